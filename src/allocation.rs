@@ -0,0 +1,295 @@
+//! Counting of heap allocations during timed sections.
+//!
+//! [`CountingAllocator`] wraps another global allocator and counts allocations,
+//! deallocations and bytes process-wide. Once it is installed as the
+//! `#[global_allocator]`, timers can report the allocations of a task next to its
+//! duration, see [`Timer::set_alloc_tracking`](crate::Timer::set_alloc_tracking).
+//!
+//! # Examples
+//!
+//! ```
+//! use tea_timer::allocation::CountingAllocator;
+//! use tea_timer::Timer;
+//! use std::alloc::System;
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator = CountingAllocator::new(System);
+//!
+//! let mut timer = Timer::new("Collect");
+//! timer.set_alloc_tracking(true);
+//! let v: Vec<u64> = (0..1000).collect();
+//! assert!(timer.alloc_stats().unwrap().bytes_allocated >= 8000);
+//! timer.stop(); // This will print e.g. "Collect took 5.20µs (1 alloc, 0 deallocs, 7.81 KiB allocated, 7.81 KiB peak)"
+//! # drop(v);
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::display;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static BYTES_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+/// Bytes currently allocated.
+static CURRENT: AtomicU64 = AtomicU64::new(0);
+/// Highest value of `CURRENT` since the process started.
+static PEAK: AtomicU64 = AtomicU64::new(0);
+
+/// Maximum number of sections tracking their peak at the same time.
+const SECTION_SLOTS: usize = 64;
+/// Bit mask of the slots in `SECTION_PEAKS` used by active sections.
+static ACTIVE_SECTIONS: AtomicU64 = AtomicU64::new(0);
+/// Highest value of `CURRENT` since the section using the slot started.
+static SECTION_PEAKS: [AtomicU64; SECTION_SLOTS] = [const { AtomicU64::new(0) }; SECTION_SLOTS];
+
+/// A global allocator which counts allocations before delegating to another allocator.
+///
+/// The counting costs a few relaxed atomic operations per allocation, plus one for each
+/// active tracked section.
+#[derive(Debug, Default)]
+pub struct CountingAllocator<A = System> {
+    inner: A,
+}
+
+impl<A> CountingAllocator<A> {
+    /// Wraps the given allocator, usually [`System`].
+    #[inline]
+    pub const fn new(inner: A) -> Self {
+        CountingAllocator { inner }
+    }
+}
+
+#[inline]
+fn on_alloc(size: usize) {
+    let size = size as u64;
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES_ALLOCATED.fetch_add(size, Ordering::Relaxed);
+    let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(current, Ordering::Relaxed);
+    let mut active = ACTIVE_SECTIONS.load(Ordering::Relaxed);
+    while active != 0 {
+        SECTION_PEAKS[active.trailing_zeros() as usize].fetch_max(current, Ordering::Relaxed);
+        active &= active - 1;
+    }
+}
+
+#[inline]
+fn on_dealloc(size: usize) {
+    DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    CURRENT.fetch_sub(size as u64, Ordering::Relaxed);
+}
+
+// SAFETY: all calls are forwarded unchanged to the inner allocator
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    #[inline]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    #[inline]
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            on_alloc(layout.size());
+        }
+        ptr
+    }
+
+    #[inline]
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        on_dealloc(layout.size());
+    }
+
+    #[inline]
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+        // a reallocation counts as a deallocation of the old block and an allocation of the new one
+        if !new_ptr.is_null() {
+            on_dealloc(layout.size());
+            on_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Allocation counts of a section, or of the whole process for [`totals`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    pub allocations: u64,
+    pub deallocations: u64,
+    /// Total size of all allocations, including memory freed again.
+    pub bytes_allocated: u64,
+    /// Highest number of bytes allocated at the same time, above the level at the
+    /// start of the section.
+    pub peak_bytes: u64,
+}
+
+impl fmt::Display for AllocStats {
+    /// Formats the counts as e.g. `12 allocs, 10 deallocs, 4.00 KiB allocated, 2.00 KiB peak`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} alloc{}, {} dealloc{}, {} allocated, {} peak",
+            self.allocations,
+            if self.allocations == 1 { "" } else { "s" },
+            self.deallocations,
+            if self.deallocations == 1 { "" } else { "s" },
+            display::format_bytes(self.bytes_allocated),
+            display::format_bytes(self.peak_bytes)
+        )
+    }
+}
+
+/// Returns the counts since the process started, with the peak of currently allocated bytes.
+///
+/// All counts are zero unless [`CountingAllocator`] is the global allocator.
+pub fn totals() -> AllocStats {
+    AllocStats {
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+        bytes_allocated: BYTES_ALLOCATED.load(Ordering::Relaxed),
+        peak_bytes: PEAK.load(Ordering::Relaxed),
+    }
+}
+
+/// Returns the number of bytes currently allocated.
+#[inline]
+pub fn current_bytes() -> u64 {
+    CURRENT.load(Ordering::Relaxed)
+}
+
+/// The counts at the start of a tracked section.
+///
+/// Each section tracks its own peak in one of 64 slots, so sections on different threads
+/// do not interfere. While all slots are taken, further sections report the bytes
+/// allocated above their start at the time of the report as their peak.
+#[derive(Debug)]
+pub(crate) struct AllocSection {
+    start: AllocStats,
+    start_current: u64,
+    /// The slot in `SECTION_PEAKS`, if one was free.
+    slot: Option<usize>,
+}
+
+impl AllocSection {
+    pub(crate) fn start() -> Self {
+        let slot = claim_slot();
+        let start_current = current_bytes();
+        // only the owner of a slot stores to it, other threads merely raise the peak
+        if let Some(slot) = slot {
+            SECTION_PEAKS[slot].store(start_current, Ordering::Relaxed);
+        }
+        AllocSection {
+            start: totals(),
+            start_current,
+            slot,
+        }
+    }
+
+    /// Returns the counts since the section started.
+    pub(crate) fn stats(&self) -> AllocStats {
+        let now = totals();
+        let peak = match self.slot {
+            Some(slot) => SECTION_PEAKS[slot].load(Ordering::Relaxed),
+            None => current_bytes(),
+        };
+        AllocStats {
+            allocations: now.allocations.saturating_sub(self.start.allocations),
+            deallocations: now.deallocations.saturating_sub(self.start.deallocations),
+            bytes_allocated: now
+                .bytes_allocated
+                .saturating_sub(self.start.bytes_allocated),
+            peak_bytes: peak.saturating_sub(self.start_current),
+        }
+    }
+}
+
+impl Drop for AllocSection {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            ACTIVE_SECTIONS.fetch_and(!(1 << slot), Ordering::Relaxed);
+        }
+    }
+}
+
+/// Claims a free peak slot, returning its index.
+fn claim_slot() -> Option<usize> {
+    ACTIVE_SECTIONS
+        .fetch_update(Ordering::AcqRel, Ordering::Relaxed, |active| {
+            let slot = (!active).trailing_zeros() as usize;
+            (slot < SECTION_SLOTS).then(|| active | 1 << slot)
+        })
+        .ok()
+        .map(|active| (!active).trailing_zeros() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Timer;
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator::new(System);
+
+    #[test]
+    fn test_alloc_section() {
+        // other tests allocate concurrently, so the counts are lower bounds
+        let outer = AllocSection::start();
+        let block = vec![0u8; 1 << 20];
+        let inner = AllocSection::start();
+        let mut small = Vec::with_capacity(16);
+        small.push(1u64);
+        drop(small);
+        let stats = inner.stats();
+        assert!(stats.allocations >= 1);
+        assert!(stats.deallocations >= 1);
+        assert!(stats.bytes_allocated >= 128);
+        drop(inner);
+        drop(block);
+        // a section on another thread does not reset the peak of this one
+        std::thread::spawn(|| AllocSection::start().stats())
+            .join()
+            .unwrap();
+        let stats = outer.stats();
+        assert!(stats.allocations >= 2);
+        assert!(stats.bytes_allocated >= 1 << 20);
+        // the peak of the outer section survives the nested section
+        assert!(stats.peak_bytes >= 1 << 20);
+    }
+
+    #[test]
+    fn test_alloc_stats_display() {
+        let stats = AllocStats {
+            allocations: 1,
+            deallocations: 2,
+            bytes_allocated: 4096,
+            peak_bytes: 100,
+        };
+        assert_eq!(
+            stats.to_string(),
+            "1 alloc, 2 deallocs, 4.00 KiB allocated, 100 B peak"
+        );
+    }
+
+    #[test]
+    fn test_timer_alloc_tracking() {
+        let mut timer = Timer::new("Alloc");
+        assert!(timer.alloc_stats().is_none());
+        timer.set_alloc_tracking(true);
+        let v = vec![1u8; 4096];
+        let stats = timer.alloc_stats().unwrap();
+        assert!(stats.bytes_allocated >= 4096);
+        let took = timer.took_str();
+        assert!(took.starts_with("Alloc took "), "{took}");
+        assert!(took.contains(" allocated, "), "{took}");
+        drop(v);
+        assert_eq!(crate::took!(@allocations = true; vec![0u8; 8].len()), 8);
+    }
+}
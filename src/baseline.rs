@@ -0,0 +1,493 @@
+//! Baselines of aggregated timings and regression comparison.
+//!
+//! A [`Baseline`] keeps the number of measurements, mean and standard deviation of
+//! every task, taken from the [`registry`] or from benchmark results.
+//! It can be saved to a file and compared with a later run: the change of every task
+//! is tested with Welch's t-test, and significant changes beyond a tolerance are
+//! flagged as regressions or improvements.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use tea_timer::baseline::Baseline;
+//! use tea_timer::{registry, took};
+//!
+//! registry::set_recording(true);
+//! for i in 0..1000 {
+//!     took(|| i * 2, "parse");
+//! }
+//! let current = Baseline::from_registry();
+//! if let Ok(baseline) = Baseline::load("timings.baseline") {
+//!     let comparison = baseline.compare(&current, 0.05);
+//!     comparison.report(); // prints the change of every task
+//!     assert!(!comparison.has_regressions());
+//! }
+//! current.save("timings.baseline").unwrap();
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::bench::BenchResult;
+use crate::registry::{self, Stats};
+use crate::{display, sink};
+
+/// The significance level below which a change is considered real rather than noise.
+pub const SIGNIFICANCE: f64 = 0.05;
+
+const HEADER: &str = "# tea-timer baseline v1";
+
+/// The aggregated measurements of one task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskSummary {
+    /// Number of measurements.
+    pub count: u64,
+    pub mean: Duration,
+    /// Sample standard deviation.
+    pub std_dev: Duration,
+}
+
+impl From<&Stats> for TaskSummary {
+    #[inline]
+    fn from(stats: &Stats) -> Self {
+        TaskSummary {
+            count: stats.count(),
+            mean: stats.mean(),
+            std_dev: stats.std_dev(),
+        }
+    }
+}
+
+impl From<&BenchResult> for TaskSummary {
+    #[inline]
+    fn from(result: &BenchResult) -> Self {
+        TaskSummary {
+            count: result.samples.len() as u64,
+            mean: result.mean,
+            std_dev: result.std_dev,
+        }
+    }
+}
+
+/// Aggregated timings of a run, keyed by task name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Baseline {
+    tasks: BTreeMap<String, TaskSummary>,
+}
+
+impl Baseline {
+    /// Creates an empty baseline.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a baseline from the statistics in the [`registry`].
+    pub fn from_registry() -> Self {
+        let tasks = registry::snapshot()
+            .iter()
+            .map(|(name, stats)| (name.clone(), TaskSummary::from(stats)))
+            .collect();
+        Baseline { tasks }
+    }
+
+    /// Creates a baseline from benchmark results.
+    pub fn from_results(results: &[BenchResult]) -> Self {
+        let tasks = results
+            .iter()
+            .map(|result| (result.name.clone(), TaskSummary::from(result)))
+            .collect();
+        Baseline { tasks }
+    }
+
+    /// Adds or replaces the summary of a task.
+    #[inline]
+    pub fn insert(&mut self, task_name: &str, summary: TaskSummary) {
+        self.tasks.insert(task_name.to_string(), summary);
+    }
+
+    /// Returns the summary of a task.
+    #[inline]
+    pub fn get(&self, task_name: &str) -> Option<&TaskSummary> {
+        self.tasks.get(task_name)
+    }
+
+    /// Returns the tasks and their summaries, sorted by name.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TaskSummary)> {
+        self.tasks
+            .iter()
+            .map(|(name, summary)| (name.as_str(), summary))
+    }
+
+    /// Writes the baseline in a line-based text format, one task per line.
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{HEADER}")?;
+        for (name, summary) in &self.tasks {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}",
+                summary.count,
+                summary.mean.as_nanos(),
+                summary.std_dev.as_nanos(),
+                name.replace(['\n', '\r'], " ")
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reads a baseline written by [`Baseline::write`].
+    pub fn read(reader: impl Read) -> io::Result<Self> {
+        fn invalid(line: usize, message: &str) -> io::Error {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid baseline at line {line}: {message}"),
+            )
+        }
+
+        let mut lines = BufReader::new(reader).lines();
+        match lines.next().transpose()? {
+            Some(header) if header == HEADER => {}
+            _ => return Err(invalid(1, "missing header")),
+        }
+        let mut baseline = Baseline::new();
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.splitn(4, '\t');
+            let mut number = |field: &str| {
+                fields
+                    .next()
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .ok_or_else(|| invalid(i + 2, &format!("invalid {field}")))
+            };
+            let summary = TaskSummary {
+                count: number("count")?,
+                mean: Duration::from_nanos(number("mean")?),
+                std_dev: Duration::from_nanos(number("standard deviation")?),
+            };
+            let name = fields
+                .next()
+                .ok_or_else(|| invalid(i + 2, "missing name"))?;
+            baseline.insert(name, summary);
+        }
+        Ok(baseline)
+    }
+
+    /// Saves the baseline to a file at `path`, replacing it if it exists.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Loads a baseline saved with [`Baseline::save`].
+    #[inline]
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(File::open(path)?)
+    }
+
+    /// Compares the tasks present in both this baseline and `current`.
+    ///
+    /// A task regressed if its mean grew by more than `tolerance` (e.g. `0.05` for 5%)
+    /// and the change is significant at the [`SIGNIFICANCE`] level.
+    pub fn compare(&self, current: &Baseline, tolerance: f64) -> Comparison {
+        let changes = self
+            .tasks
+            .iter()
+            .filter_map(|(name, baseline)| {
+                let current = current.tasks.get(name)?;
+                Some(Change::new(name, *baseline, *current, tolerance))
+            })
+            .collect();
+        Comparison { changes }
+    }
+}
+
+/// The verdict of a [`Change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Significantly slower, beyond the tolerance.
+    Regressed,
+    /// Significantly faster, beyond the tolerance.
+    Improved,
+    /// Within the tolerance or not significant.
+    Unchanged,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Verdict::Regressed => "regressed",
+            Verdict::Improved => "improved",
+            Verdict::Unchanged => "unchanged",
+        })
+    }
+}
+
+/// The change of one task between a baseline and the current run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub name: String,
+    pub baseline: TaskSummary,
+    pub current: TaskSummary,
+    /// Relative change of the mean, e.g. `0.1` if the current run is 10% slower.
+    pub relative: f64,
+    /// Two-sided p-value of Welch's t-test for a difference of the means.
+    pub p_value: f64,
+    pub verdict: Verdict,
+}
+
+impl Change {
+    fn new(name: &str, baseline: TaskSummary, current: TaskSummary, tolerance: f64) -> Self {
+        let (old, new) = (nanos(baseline.mean), nanos(current.mean));
+        let relative = if old > 0. { (new - old) / old } else { 0. };
+        let p_value = welch_p_value(&baseline, &current);
+        let verdict = if p_value >= SIGNIFICANCE || relative.abs() <= tolerance {
+            Verdict::Unchanged
+        } else if relative > 0. {
+            Verdict::Regressed
+        } else {
+            Verdict::Improved
+        };
+        Change {
+            name: name.to_string(),
+            baseline,
+            current,
+            relative,
+            p_value,
+            verdict,
+        }
+    }
+}
+
+/// The result of [`Baseline::compare`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    /// The change of every task present in both runs, sorted by name.
+    pub changes: Vec<Change>,
+}
+
+impl Comparison {
+    /// Returns the tasks which regressed.
+    #[inline]
+    pub fn regressions(&self) -> impl Iterator<Item = &Change> {
+        self.changes
+            .iter()
+            .filter(|change| change.verdict == Verdict::Regressed)
+    }
+
+    /// Returns `true` if any task regressed.
+    #[inline]
+    pub fn has_regressions(&self) -> bool {
+        self.regressions().next().is_some()
+    }
+
+    /// Returns a table with the means, relative change, p-value and verdict of every task.
+    pub fn report_str(&self) -> String {
+        let mut rows =
+            vec![["name", "baseline", "current", "change", "p", "verdict"].map(String::from)];
+        for change in &self.changes {
+            rows.push([
+                change.name.clone(),
+                display::format_duration(change.baseline.mean),
+                display::format_duration(change.current.mean),
+                format!("{:+.1}%", change.relative * 100.),
+                format!("{:.3}", change.p_value),
+                change.verdict.to_string(),
+            ]);
+        }
+        display::format_table(&rows)
+    }
+
+    /// Prints the comparison table to the global [`sink`].
+    #[inline]
+    pub fn report(&self) {
+        sink::emit(None, &self.report_str());
+    }
+}
+
+#[inline]
+fn nanos(duration: Duration) -> f64 {
+    duration.as_nanos() as f64
+}
+
+/// Returns the two-sided p-value of Welch's t-test for the means of two summaries.
+fn welch_p_value(a: &TaskSummary, b: &TaskSummary) -> f64 {
+    if a.count < 2 || b.count < 2 {
+        return 1.;
+    }
+    let (na, nb) = (a.count as f64, b.count as f64);
+    let (va, vb) = (nanos(a.std_dev).powi(2) / na, nanos(b.std_dev).powi(2) / nb);
+    let diff = nanos(b.mean) - nanos(a.mean);
+    if va + vb == 0. {
+        return if diff == 0. { 1. } else { 0. };
+    }
+    let t = diff / (va + vb).sqrt();
+    // Welch–Satterthwaite degrees of freedom
+    let df = (va + vb).powi(2) / (va.powi(2) / (na - 1.) + vb.powi(2) / (nb - 1.));
+    student_t_p_value(t, df)
+}
+
+/// Returns the two-sided p-value of Student's t distribution with `df` degrees of freedom.
+fn student_t_p_value(t: f64, df: f64) -> f64 {
+    regularized_incomplete_beta(df / (df + t * t), df / 2., 0.5)
+}
+
+/// Returns the natural logarithm of the gamma function, using the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1. - x);
+    }
+    let x = x - 1.;
+    let t = x + 7.5;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| sum + c / (x + i as f64 + 1.));
+    0.5 * (2. * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// Returns the regularized incomplete beta function `I_x(a, b)`.
+fn regularized_incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0. {
+        return 0.;
+    }
+    if x >= 1. {
+        return 1.;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1. - x).ln()).exp();
+    // the continued fraction converges quickly on this side of the mean
+    if x < (a + 1.) / (a + b + 2.) {
+        front * beta_continued_fraction(x, a, b) / a
+    } else {
+        1. - front * beta_continued_fraction(1. - x, b, a) / b
+    }
+}
+
+/// Evaluates the continued fraction of the incomplete beta function with Lentz's method.
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let clamp = |v: f64| if v.abs() < TINY { TINY } else { v };
+    let mut c = 1.;
+    let mut d = 1. / clamp(1. - (a + b) * x / (a + 1.));
+    let mut h = d;
+    for m in 1..=300 {
+        let m = m as f64;
+        let numerator = m * (b - m) * x / ((a + 2. * m - 1.) * (a + 2. * m));
+        d = 1. / clamp(1. + numerator * d);
+        c = clamp(1. + numerator / c);
+        h *= d * c;
+        let numerator = -(a + m) * (a + b + m) * x / ((a + 2. * m) * (a + 2. * m + 1.));
+        d = 1. / clamp(1. + numerator * d);
+        c = clamp(1. + numerator / c);
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.).abs() < 1e-15 {
+            break;
+        }
+    }
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(count: u64, mean_micros: u64, std_dev_micros: u64) -> TaskSummary {
+        TaskSummary {
+            count,
+            mean: Duration::from_micros(mean_micros),
+            std_dev: Duration::from_micros(std_dev_micros),
+        }
+    }
+
+    #[test]
+    fn test_student_t_p_value() {
+        assert!((student_t_p_value(0., 10.) - 1.).abs() < 1e-12);
+        assert!((student_t_p_value(2., 10.) - 0.073_388).abs() < 1e-5);
+        assert!((student_t_p_value(-2., 10.) - 0.073_388).abs() < 1e-5);
+        assert!((student_t_p_value(1.959_964, 1e6) - 0.05).abs() < 1e-4);
+        assert!((ln_gamma(5.) - 24f64.ln()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_baseline_round_trip() {
+        let mut baseline = Baseline::new();
+        baseline.insert("parse", summary(100, 250, 10));
+        baseline.insert("load\tall\nfiles", summary(3, 1_000, 0));
+        let mut buffer = Vec::new();
+        baseline.write(&mut buffer).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.clone()).unwrap(),
+            "# tea-timer baseline v1\n3\t1000000\t0\tload\tall files\n100\t250000\t10000\tparse\n"
+        );
+        let read = Baseline::read(&buffer[..]).unwrap();
+        assert_eq!(read.get("parse"), baseline.get("parse"));
+        assert_eq!(read.get("load\tall files"), Some(&summary(3, 1_000, 0)));
+        let error = Baseline::read(&b"# tea-timer baseline v1\n1\tx\t0\tname\n"[..]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid baseline at line 2: invalid mean"
+        );
+        assert!(Baseline::read(&b"1\t1\t0\tname\n"[..]).is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut old = Baseline::new();
+        old.insert("slower", summary(50, 100, 5));
+        old.insert("faster", summary(50, 100, 5));
+        old.insert("noisy", summary(5, 100, 50));
+        old.insert("tolerated", summary(50, 100, 1));
+        old.insert("removed", summary(50, 100, 1));
+        let mut new = Baseline::new();
+        new.insert("slower", summary(50, 120, 5));
+        new.insert("faster", summary(50, 80, 5));
+        new.insert("noisy", summary(5, 130, 50));
+        new.insert("tolerated", summary(50, 103, 1));
+        new.insert("added", summary(50, 100, 1));
+
+        let comparison = old.compare(&new, 0.05);
+        let verdicts: Vec<_> = comparison
+            .changes
+            .iter()
+            .map(|change| (change.name.as_str(), change.verdict))
+            .collect();
+        assert_eq!(
+            verdicts,
+            [
+                ("faster", Verdict::Improved),
+                ("noisy", Verdict::Unchanged),
+                ("slower", Verdict::Regressed),
+                ("tolerated", Verdict::Unchanged),
+            ]
+        );
+        assert!(comparison.has_regressions());
+        assert_eq!(comparison.regressions().count(), 1);
+        assert!(comparison.changes[3].p_value < SIGNIFICANCE);
+        assert_eq!(
+            comparison.report_str().lines().nth(3).unwrap(),
+            "slower     100.00µs  120.00µs  +20.0%  0.000  regressed"
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! A small benchmark harness for ordinary binaries.
+//!
+//! [`bench`](fn@bench) runs a closure for a warmup period, picks the number of iterations per
+//! sample so that all samples fit the measurement time, and summarizes the time per
+//! iteration with robust statistics and outliers flagged by Tukey's fences.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use tea_timer::bench::bench;
+//!
+//! let result = bench("sum", || (0..1000u64).sum::<u64>());
+//! // This will print e.g.
+//! // sum  median 312ns  mean 315ns  std 8ns  MAD 3ns
+//! //   50 samples of 3154 iterations, 2 outliers (1 high mild, 1 high severe)
+//! assert!(result.min <= result.median);
+//! ```
+
+use std::fmt;
+use std::hint::black_box;
+use std::time::Duration;
+
+use crate::{display, sink, Clock, InstantClock};
+
+/// Options of a benchmark run.
+///
+/// By default the closure is warmed up for 500ms, then 50 samples are taken
+/// within a measurement time of 2s.
+#[derive(Debug, Clone)]
+pub struct Bench<C: Clock = InstantClock> {
+    clock: C,
+    warmup: Duration,
+    measurement: Duration,
+    samples: usize,
+}
+
+impl Default for Bench {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bench {
+    /// Creates a benchmark with the default options.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_clock(InstantClock)
+    }
+}
+
+impl<C: Clock> Bench<C> {
+    /// Creates a benchmark with the default options, reading time from the given clock.
+    #[inline]
+    pub fn with_clock(clock: C) -> Self {
+        Bench {
+            clock,
+            warmup: Duration::from_millis(500),
+            measurement: Duration::from_secs(2),
+            samples: 50,
+        }
+    }
+
+    /// Sets how long the closure runs before measuring.
+    #[inline]
+    pub fn warmup(mut self, warmup: Duration) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Sets the target time of all samples together.
+    #[inline]
+    pub fn measurement_time(mut self, measurement: Duration) -> Self {
+        self.measurement = measurement;
+        self
+    }
+
+    /// Sets the number of samples, at least 2 are taken.
+    #[inline]
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(2);
+        self
+    }
+
+    /// Runs the benchmark without printing anything.
+    pub fn run<F: FnMut() -> R, R>(&self, name: &str, mut f: F) -> BenchResult {
+        // warm up with doubling batches, which also estimates the time per iteration
+        let mut warmup_iterations = 0u64;
+        let mut batch = 1u64;
+        let start = self.clock.now();
+        let mut elapsed = Duration::ZERO;
+        while warmup_iterations == 0 || elapsed < self.warmup {
+            for _ in 0..batch {
+                black_box(f());
+            }
+            warmup_iterations += batch;
+            batch = batch.saturating_mul(2);
+            elapsed = self.clock.elapsed(start);
+        }
+        let per_iteration = elapsed.as_nanos() as f64 / warmup_iterations as f64;
+        let per_sample = self.measurement.as_nanos() as f64 / self.samples as f64;
+        let iterations = if per_iteration > 0. {
+            ((per_sample / per_iteration) as u64).max(1)
+        } else {
+            warmup_iterations
+        };
+
+        let samples = (0..self.samples)
+            .map(|_| {
+                let start = self.clock.now();
+                for _ in 0..iterations {
+                    black_box(f());
+                }
+                self.clock.elapsed(start).as_nanos() as f64 / iterations as f64
+            })
+            .collect();
+        BenchResult::new(name, iterations, samples)
+    }
+
+    /// Runs the benchmark and prints its summary to the global sink.
+    #[inline]
+    pub fn bench<F: FnMut() -> R, R>(&self, name: &str, f: F) -> BenchResult {
+        let result = self.run(name, f);
+        sink::emit(None, &result.to_string());
+        result
+    }
+}
+
+/// Benchmarks `f` with the default options and prints the summary to the global sink,
+/// see [`Bench`].
+#[inline]
+pub fn bench<F: FnMut() -> R, R>(name: &str, f: F) -> BenchResult {
+    Bench::new().bench(name, f)
+}
+
+/// Number of samples outside of Tukey's fences.
+///
+/// Mild outliers are more than 1.5 times the interquartile range below the first
+/// or above the third quartile, severe outliers more than 3 times.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Outliers {
+    pub low_severe: usize,
+    pub low_mild: usize,
+    pub high_mild: usize,
+    pub high_severe: usize,
+}
+
+impl Outliers {
+    /// Returns the total number of outliers.
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.low_severe + self.low_mild + self.high_mild + self.high_severe
+    }
+}
+
+/// The result of a benchmark, with statistics of the time per iteration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchResult {
+    pub name: String,
+    /// Number of iterations timed together in each sample.
+    pub iterations: u64,
+    /// Time per iteration of each sample, in the order they were taken.
+    pub samples: Vec<Duration>,
+    pub mean: Duration,
+    pub median: Duration,
+    /// Sample standard deviation.
+    pub std_dev: Duration,
+    /// Median absolute deviation from the median.
+    pub mad: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    pub outliers: Outliers,
+}
+
+impl BenchResult {
+    /// Computes the statistics of samples given as nanoseconds per iteration.
+    fn new(name: &str, iterations: u64, samples: Vec<f64>) -> Self {
+        let mut sorted = samples.clone();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / n;
+        let variance = sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.).max(1.);
+        let median = quantile(&sorted, 0.5);
+        let mut deviations: Vec<f64> = sorted.iter().map(|x| (x - median).abs()).collect();
+        deviations.sort_by(f64::total_cmp);
+
+        let (q1, q3) = (quantile(&sorted, 0.25), quantile(&sorted, 0.75));
+        let iqr = q3 - q1;
+        let mut outliers = Outliers::default();
+        for &x in &sorted {
+            if x < q1 - 3. * iqr {
+                outliers.low_severe += 1;
+            } else if x < q1 - 1.5 * iqr {
+                outliers.low_mild += 1;
+            } else if x > q3 + 3. * iqr {
+                outliers.high_severe += 1;
+            } else if x > q3 + 1.5 * iqr {
+                outliers.high_mild += 1;
+            }
+        }
+
+        BenchResult {
+            name: name.to_string(),
+            iterations,
+            mean: nanos(mean),
+            median: nanos(median),
+            std_dev: nanos(variance.sqrt()),
+            mad: nanos(quantile(&deviations, 0.5)),
+            min: nanos(sorted[0]),
+            max: nanos(sorted[sorted.len() - 1]),
+            outliers,
+            samples: samples.into_iter().map(nanos).collect(),
+        }
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}  median {}  mean {}  std {}  MAD {}\n  {} samples of {} iterations",
+            self.name,
+            display::format_duration(self.median),
+            display::format_duration(self.mean),
+            display::format_duration(self.std_dev),
+            display::format_duration(self.mad),
+            self.samples.len(),
+            self.iterations
+        )?;
+        let outliers = &self.outliers;
+        if outliers.total() == 0 {
+            return Ok(());
+        }
+        let kinds = [
+            (outliers.low_severe, "low severe"),
+            (outliers.low_mild, "low mild"),
+            (outliers.high_mild, "high mild"),
+            (outliers.high_severe, "high severe"),
+        ];
+        let kinds: Vec<_> = kinds
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, kind)| format!("{count} {kind}"))
+            .collect();
+        write!(
+            f,
+            ", {} outlier{} ({})",
+            outliers.total(),
+            if outliers.total() == 1 { "" } else { "s" },
+            kinds.join(", ")
+        )
+    }
+}
+
+/// Returns the `q` quantile of sorted values, interpolating linearly between neighbours.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let rank = q * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+#[inline]
+fn nanos(nanos: f64) -> Duration {
+    Duration::from_nanos(nanos.round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ManualClock;
+
+    #[test]
+    fn test_quantile() {
+        let sorted = [1., 2., 3., 4.];
+        assert_eq!(quantile(&sorted, 0.), 1.);
+        assert_eq!(quantile(&sorted, 0.5), 2.5);
+        assert_eq!(quantile(&sorted, 0.25), 1.75);
+        assert_eq!(quantile(&sorted, 1.), 4.);
+    }
+
+    #[test]
+    fn test_bench_result() {
+        let mut samples = vec![100.; 16];
+        samples[1] = 90.;
+        samples[2] = 110.;
+        samples[3] = 104.;
+        samples[4] = 130.;
+        samples[5] = 40.;
+        let result = BenchResult::new("task", 10, samples);
+        assert_eq!(result.median, Duration::from_nanos(100));
+        assert_eq!(result.mean, Duration::from_nanos(98));
+        assert_eq!(result.mad, Duration::ZERO);
+        assert_eq!(result.min, Duration::from_nanos(40));
+        assert_eq!(result.max, Duration::from_nanos(130));
+        assert_eq!(
+            result.outliers,
+            Outliers {
+                low_severe: 2,
+                low_mild: 0,
+                high_mild: 0,
+                high_severe: 3,
+            }
+        );
+        assert_eq!(
+            result.to_string(),
+            "task  median 100ns  mean 98ns  std 18ns  MAD 0ns\n  \
+             16 samples of 10 iterations, 5 outliers (2 low severe, 3 high severe)"
+        );
+    }
+
+    #[test]
+    fn test_bench_iterations() {
+        let clock = ManualClock::new();
+        let mut calls = 0u64;
+        let result = Bench::with_clock(clock.clone())
+            .warmup(Duration::from_millis(10))
+            .measurement_time(Duration::from_millis(100))
+            .samples(10)
+            .run("step", || {
+                calls += 1;
+                clock.advance(Duration::from_micros(100));
+            });
+        // warmup runs 1 + 2 + ... + 64 iterations until 10ms have passed
+        assert_eq!(result.iterations, 100);
+        assert_eq!(calls, 127 + 10 * 100);
+        assert_eq!(result.samples, [Duration::from_micros(100); 10]);
+        assert_eq!(result.std_dev, Duration::ZERO);
+        assert_eq!(result.outliers.total(), 0);
+    }
+}
@@ -0,0 +1,278 @@
+//! Export of timings in the Chrome Trace Event Format.
+//!
+//! When recording is enabled with [`set_recording`], every scoped timer records a begin
+//! event when it is created and an end event when it finishes, which includes
+//! [`took`](fn@crate::took), the macros and `#[timed]` functions. The recorded events can
+//! be written as JSON with [`write_json`] or [`save`] and opened in `chrome://tracing`
+//! or [Perfetto](https://ui.perfetto.dev).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use tea_timer::{chrome, took};
+//!
+//! chrome::set_recording(true);
+//! took(|| took(|| 1 + 1, "inner"), "outer");
+//! chrome::set_recording(false);
+//! chrome::save("trace.json").unwrap();
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::lock_ignoring_poison;
+
+/// The phase of a [`TraceEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// The start of a timed section, `"B"` in the JSON output.
+    Begin,
+    /// The end of a timed section, `"E"` in the JSON output.
+    End,
+}
+
+impl Phase {
+    #[inline]
+    fn code(self) -> &'static str {
+        match self {
+            Phase::Begin => "B",
+            Phase::End => "E",
+        }
+    }
+}
+
+/// A recorded begin or end event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// The task name of the timer.
+    pub name: String,
+    pub phase: Phase,
+    /// The time of the event since the first event recorded by the process.
+    pub timestamp: Duration,
+    /// A small number identifying the thread the section began on.
+    pub thread_id: u64,
+}
+
+static EVENTS: Mutex<Vec<TraceEvent>> = Mutex::new(Vec::new());
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+#[inline]
+fn events() -> MutexGuard<'static, Vec<TraceEvent>> {
+    lock_ignoring_poison(&EVENTS)
+}
+
+/// Returns the id of the current thread, numbered in order of first use.
+fn thread_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static ID: u64 = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    }
+    ID.with(|id| *id)
+}
+
+fn push(name: &str, phase: Phase, thread_id: u64) {
+    let epoch = *EPOCH.get_or_init(Instant::now);
+    let event = TraceEvent {
+        name: name.to_string(),
+        phase,
+        timestamp: epoch.elapsed(),
+        thread_id,
+    };
+    events().push(event);
+}
+
+/// Records a begin event on the current thread if recording, returning the thread id
+/// to pass to [`end`].
+pub(crate) fn begin(name: &str) -> Option<u64> {
+    if !is_recording() {
+        return None;
+    }
+    let thread_id = thread_id();
+    push(name, Phase::Begin, thread_id);
+    Some(thread_id)
+}
+
+/// Records the end event of a section begun on the given thread.
+///
+/// The end event is recorded even if recording was disabled meanwhile, so sections are balanced.
+#[inline]
+pub(crate) fn end(name: &str, thread_id: u64) {
+    push(name, Phase::End, thread_id);
+}
+
+/// Enables or disables recording of begin and end events of scoped timers.
+#[inline]
+pub fn set_recording(enabled: bool) {
+    RECORDING.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns `true` if begin and end events of scoped timers are recorded.
+#[inline]
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// Returns the recorded events.
+#[inline]
+pub fn snapshot() -> Vec<TraceEvent> {
+    events().clone()
+}
+
+/// Returns the recorded events and clears the buffer.
+#[inline]
+pub fn take() -> Vec<TraceEvent> {
+    std::mem::take(&mut *events())
+}
+
+/// Removes all recorded events.
+#[inline]
+pub fn reset() {
+    events().clear();
+}
+
+/// Writes the recorded events as Chrome Trace Event Format JSON.
+#[inline]
+pub fn write_json(writer: impl Write) -> io::Result<()> {
+    write_events(writer, &snapshot())
+}
+
+/// Writes the recorded events as Chrome Trace Event Format JSON to a new file at `path`.
+pub fn save(path: impl AsRef<Path>) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_json(&mut writer)?;
+    writer.flush()
+}
+
+/// Writes the given events as Chrome Trace Event Format JSON.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::chrome::{write_events, Phase, TraceEvent};
+/// use std::time::Duration;
+///
+/// let event = TraceEvent {
+///     name: "load".to_string(),
+///     phase: Phase::Begin,
+///     timestamp: Duration::from_nanos(1500),
+///     thread_id: 1,
+/// };
+/// let mut json = Vec::new();
+/// write_events(&mut json, &[event]).unwrap();
+/// let json = String::from_utf8(json).unwrap();
+/// assert!(json.contains(r#"{"name":"load","ph":"B","ts":1.500,"#));
+/// ```
+pub fn write_events(mut writer: impl Write, events: &[TraceEvent]) -> io::Result<()> {
+    let pid = std::process::id();
+    writer.write_all(b"{\"traceEvents\":[")?;
+    for (i, event) in events.iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        let nanos = event.timestamp.as_nanos();
+        write!(
+            writer,
+            "\n{{\"name\":\"{}\",\"ph\":\"{}\",\"ts\":{}.{:03},\"pid\":{},\"tid\":{}}}",
+            escape(&event.name),
+            event.phase.code(),
+            nanos / 1000,
+            nanos % 1000,
+            pid,
+            event.thread_id
+        )?;
+    }
+    writer.write_all(b"\n],\"displayTimeUnit\":\"ms\"}\n")
+}
+
+/// Escapes a string for use inside a JSON string literal.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::took;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("a \"b\" \\ c\n\u{1}é"),
+            "a \\\"b\\\" \\\\ c\\n\\u0001é"
+        );
+    }
+
+    #[test]
+    fn test_write_events() {
+        let events = [
+            TraceEvent {
+                name: "outer".to_string(),
+                phase: Phase::Begin,
+                timestamp: Duration::from_nanos(1_000),
+                thread_id: 1,
+            },
+            TraceEvent {
+                name: "outer".to_string(),
+                phase: Phase::End,
+                timestamp: Duration::from_nanos(2_345_678),
+                thread_id: 1,
+            },
+        ];
+        let mut json = Vec::new();
+        write_events(&mut json, &events).unwrap();
+        let pid = std::process::id();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            format!(
+                "{{\"traceEvents\":[\n\
+                 {{\"name\":\"outer\",\"ph\":\"B\",\"ts\":1.000,\"pid\":{pid},\"tid\":1}},\n\
+                 {{\"name\":\"outer\",\"ph\":\"E\",\"ts\":2345.678,\"pid\":{pid},\"tid\":1}}\n\
+                 ],\"displayTimeUnit\":\"ms\"}}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_recording() {
+        set_recording(true);
+        took(|| took(|| (), "chrome inner"), "chrome outer");
+        set_recording(false);
+        // other tests may record concurrently
+        let events: Vec<_> = snapshot()
+            .into_iter()
+            .filter(|event| event.name.starts_with("chrome "))
+            .collect();
+        let phases: Vec<_> = events
+            .iter()
+            .map(|event| (event.name.as_str(), event.phase))
+            .collect();
+        assert_eq!(
+            phases,
+            [
+                ("chrome outer", Phase::Begin),
+                ("chrome inner", Phase::Begin),
+                ("chrome inner", Phase::End),
+                ("chrome outer", Phase::End),
+            ]
+        );
+        assert!(events.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+        assert!(events.iter().all(|e| e.thread_id == events[0].thread_id));
+    }
+}
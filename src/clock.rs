@@ -0,0 +1,127 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A source of time used by [`Timer`](crate::Timer).
+///
+/// The default clock is [`InstantClock`], which is backed by [`std::time::Instant`].
+/// [`ManualClock`] can be used in tests to control the passage of time explicitly.
+pub trait Clock {
+    /// A point in time of this clock.
+    type Instant: Copy;
+
+    /// Returns the current point in time.
+    fn now(&self) -> Self::Instant;
+
+    /// Returns the duration from `earlier` to `later`, saturating to zero if `later` is before `earlier`.
+    fn duration_between(&self, earlier: Self::Instant, later: Self::Instant) -> Duration;
+
+    /// Returns the duration elapsed since `earlier`.
+    #[inline]
+    fn elapsed(&self, earlier: Self::Instant) -> Duration {
+        self.duration_between(earlier, self.now())
+    }
+}
+
+/// The default monotonic clock, backed by [`std::time::Instant`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InstantClock;
+
+impl Clock for InstantClock {
+    type Instant = Instant;
+
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    #[inline]
+    fn duration_between(&self, earlier: Instant, later: Instant) -> Duration {
+        later.saturating_duration_since(earlier)
+    }
+}
+
+/// A clock which only moves when it is explicitly advanced.
+///
+/// Clones share the same time, so a clone can be handed to a [`Timer`](crate::Timer)
+/// while the original is used to advance the time.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::{ManualClock, Timer};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let timer = Timer::with_clock("Task", clock.clone());
+/// clock.advance(Duration::from_millis(12));
+/// assert_eq!(timer.duration(), Duration::from_millis(12));
+/// assert_eq!(timer.took_str(), "Task took 12.00ms");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// Creates a new clock starting at zero.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by `duration`.
+    #[inline]
+    pub fn advance(&self, duration: Duration) {
+        self.nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    /// Sets the time elapsed since the clock was created.
+    #[inline]
+    pub fn set(&self, elapsed: Duration) {
+        self.nanos
+            .store(elapsed.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    type Instant = Duration;
+
+    #[inline]
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+
+    #[inline]
+    fn duration_between(&self, earlier: Duration, later: Duration) -> Duration {
+        later.saturating_sub(earlier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let shared = clock.clone();
+        shared.advance(Duration::from_millis(3));
+        assert_eq!(clock.elapsed(start), Duration::from_millis(3));
+        clock.set(Duration::from_secs(1));
+        assert_eq!(shared.now(), Duration::from_secs(1));
+        assert_eq!(
+            clock.duration_between(Duration::from_secs(2), start),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_instant_clock() {
+        let clock = InstantClock;
+        let start = clock.now();
+        assert!(clock.duration_between(start, clock.now()) < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use crate::Clock;
+
+/// CPU time consumed in user and system mode.
+///
+/// This type is only available on Linux when the `cpu-time` feature is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::CpuTime;
+///
+/// let start = CpuTime::process();
+/// let sum: u64 = (0..1_000_000u64).sum();
+/// let used = CpuTime::process().saturating_sub(start);
+/// assert_eq!(used.total(), used.user + used.system);
+/// # assert!(sum > 0);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CpuTime {
+    pub user: Duration,
+    pub system: Duration,
+}
+
+impl CpuTime {
+    /// Returns the CPU time consumed by all threads of the process so far.
+    #[inline]
+    pub fn process() -> Self {
+        Self::usage(libc::RUSAGE_SELF)
+    }
+
+    /// Returns the CPU time consumed by the current thread so far.
+    #[inline]
+    pub fn thread() -> Self {
+        Self::usage(libc::RUSAGE_THREAD)
+    }
+
+    fn usage(who: libc::c_int) -> Self {
+        fn duration(time: libc::timeval) -> Duration {
+            Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1_000)
+        }
+
+        let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+        // SAFETY: `usage` points to a writable `rusage`, and `who` is a valid target
+        if unsafe { libc::getrusage(who, usage.as_mut_ptr()) } != 0 {
+            return CpuTime::default();
+        }
+        // SAFETY: `getrusage` succeeded, so `usage` is initialized
+        let usage = unsafe { usage.assume_init() };
+        CpuTime {
+            user: duration(usage.ru_utime),
+            system: duration(usage.ru_stime),
+        }
+    }
+
+    /// Returns the sum of user and system time.
+    #[inline]
+    pub fn total(&self) -> Duration {
+        self.user + self.system
+    }
+
+    /// Returns the CPU time consumed since `earlier`, saturating each part to zero.
+    #[inline]
+    pub fn saturating_sub(self, earlier: CpuTime) -> CpuTime {
+        CpuTime {
+            user: self.user.saturating_sub(earlier.user),
+            system: self.system.saturating_sub(earlier.system),
+        }
+    }
+}
+
+/// A clock measuring the CPU time of the whole process, user and system time together.
+///
+/// A [`Timer`](crate::Timer) using this clock reports how much CPU time a task used
+/// across all threads, which exceeds the wall time when the task runs in parallel.
+///
+/// This type is only available on Linux when the `cpu-time` feature is enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcessCpuClock;
+
+impl Clock for ProcessCpuClock {
+    type Instant = CpuTime;
+
+    #[inline]
+    fn now(&self) -> CpuTime {
+        CpuTime::process()
+    }
+
+    #[inline]
+    fn duration_between(&self, earlier: CpuTime, later: CpuTime) -> Duration {
+        later.saturating_sub(earlier).total()
+    }
+}
+
+/// A clock measuring the CPU time of the current thread, user and system time together.
+///
+/// The time is read from the thread calling [`Clock::now`], so a timer using this clock
+/// should not be moved to another thread.
+///
+/// This type is only available on Linux when the `cpu-time` feature is enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThreadCpuClock;
+
+impl Clock for ThreadCpuClock {
+    type Instant = CpuTime;
+
+    #[inline]
+    fn now(&self) -> CpuTime {
+        CpuTime::thread()
+    }
+
+    #[inline]
+    fn duration_between(&self, earlier: CpuTime, later: CpuTime) -> Duration {
+        later.saturating_sub(earlier).total()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Timer;
+    use std::hint::black_box;
+    use std::time::Instant;
+
+    /// Keeps the current thread busy for `duration` of wall time.
+    fn spin(duration: Duration) {
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            black_box((0..1_000u64).sum::<u64>());
+        }
+    }
+
+    #[test]
+    fn test_cpu_clocks() {
+        let process = Timer::with_clock("process", ProcessCpuClock);
+        let thread = Timer::with_clock("thread", ThreadCpuClock);
+        spin(Duration::from_millis(30));
+        let thread = thread.duration();
+        assert!(thread >= Duration::from_millis(10), "{thread:?}");
+        assert!(process.duration() >= thread);
+        let earlier = CpuTime {
+            user: Duration::from_millis(5),
+            system: Duration::from_millis(1),
+        };
+        let later = CpuTime {
+            user: Duration::from_millis(7),
+            system: Duration::ZERO,
+        };
+        assert_eq!(
+            ProcessCpuClock.duration_between(earlier, later),
+            Duration::from_millis(2)
+        );
+    }
+}
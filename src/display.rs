@@ -1,17 +1,469 @@
-const NANOS_PER_MILLI: u128 = 1_000_000;
+//! Human readable formatting of durations, sizes and throughput.
+//!
+//! [`format_duration`] formats a duration with the process-wide default
+//! [`DurationFormat`], which is used by every report of this crate and can be
+//! changed with [`set_default_format`].
+//!
+//! # Examples
+//!
+//! ```
+//! use tea_timer::display::{format_duration, DurationFormat, DurationUnit};
+//! use std::time::Duration;
+//!
+//! let duration = Duration::from_millis(83_420);
+//! assert_eq!(format_duration(duration), "83.42s");
+//! assert_eq!(DurationFormat::new().compound(true).precision(1).format(duration), "1m 23.4s");
+//! assert_eq!(DurationFormat::new().unit(DurationUnit::Millis).precision(0).format(duration), "83420ms");
+//! assert_eq!(DurationFormat::new().max_unit(DurationUnit::Days).format(duration), "1.39m");
+//! assert_eq!(DurationFormat::new().ascii(true).format(Duration::from_micros(5)), "5.00us");
+//! ```
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::{read_ignoring_poison, write_ignoring_poison};
+
 const NANOS_PER_MICRO: u128 = 1_000;
+const NANOS_PER_MILLI: u128 = 1_000_000;
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+const NANOS_PER_MIN: u128 = 60 * NANOS_PER_SEC;
+const NANOS_PER_HOUR: u128 = 60 * NANOS_PER_MIN;
+const NANOS_PER_DAY: u128 = 24 * NANOS_PER_HOUR;
+
+/// A unit a duration can be expressed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DurationUnit {
+    Nanos,
+    Micros,
+    Millis,
+    Secs,
+    Mins,
+    Hours,
+    Days,
+}
+
+impl DurationUnit {
+    const ALL: [DurationUnit; 7] = [
+        DurationUnit::Nanos,
+        DurationUnit::Micros,
+        DurationUnit::Millis,
+        DurationUnit::Secs,
+        DurationUnit::Mins,
+        DurationUnit::Hours,
+        DurationUnit::Days,
+    ];
+
+    /// Returns the number of nanoseconds in one unit.
+    #[inline]
+    pub const fn nanos(self) -> u128 {
+        match self {
+            DurationUnit::Nanos => 1,
+            DurationUnit::Micros => NANOS_PER_MICRO,
+            DurationUnit::Millis => NANOS_PER_MILLI,
+            DurationUnit::Secs => NANOS_PER_SEC,
+            DurationUnit::Mins => NANOS_PER_MIN,
+            DurationUnit::Hours => NANOS_PER_HOUR,
+            DurationUnit::Days => NANOS_PER_DAY,
+        }
+    }
 
-pub(crate) fn format_duration(duration: std::time::Duration) -> String {
-    if duration.as_secs() > 0 {
-        format!("{:.2}s", duration.as_secs_f64())
-    } else {
+    /// Returns the suffix of the unit, `ascii` selects `us` instead of `µs`.
+    #[inline]
+    pub const fn suffix(self, ascii: bool) -> &'static str {
+        match self {
+            DurationUnit::Nanos => "ns",
+            DurationUnit::Micros if ascii => "us",
+            DurationUnit::Micros => "µs",
+            DurationUnit::Millis => "ms",
+            DurationUnit::Secs => "s",
+            DurationUnit::Mins => "m",
+            DurationUnit::Hours => "h",
+            DurationUnit::Days => "d",
+        }
+    }
+}
+
+/// Options for formatting a duration.
+///
+/// The default format picks the largest unit from nanoseconds up to seconds
+/// which fits the duration and prints two decimals, e.g. `12.34ms` or `5.00s`.
+/// Nanoseconds are always printed as an integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DurationFormat {
+    precision: usize,
+    unit: Option<DurationUnit>,
+    max_unit: DurationUnit,
+    ascii: bool,
+    compound: bool,
+}
+
+impl Default for DurationFormat {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DurationFormat {
+    /// Creates the default format.
+    #[inline]
+    pub const fn new() -> Self {
+        DurationFormat {
+            precision: 2,
+            unit: None,
+            max_unit: DurationUnit::Secs,
+            ascii: false,
+            compound: false,
+        }
+    }
+
+    /// Sets the number of decimals, at most 9 are used.
+    #[inline]
+    pub const fn precision(mut self, precision: usize) -> Self {
+        self.precision = if precision > 9 { 9 } else { precision };
+        self
+    }
+
+    /// Always formats durations in the given unit.
+    #[inline]
+    pub const fn unit(mut self, unit: DurationUnit) -> Self {
+        self.unit = Some(unit);
+        self
+    }
+
+    /// Sets the largest unit picked automatically, `Secs` by default.
+    #[inline]
+    pub const fn max_unit(mut self, unit: DurationUnit) -> Self {
+        self.max_unit = unit;
+        self
+    }
+
+    /// Uses `us` instead of `µs` for microseconds.
+    #[inline]
+    pub const fn ascii(mut self, ascii: bool) -> Self {
+        self.ascii = ascii;
+        self
+    }
+
+    /// Splits durations of one second or more into days, hours, minutes and seconds,
+    /// e.g. `1h 2m 3.50s`. Zero components are omitted.
+    #[inline]
+    pub const fn compound(mut self, compound: bool) -> Self {
+        self.compound = compound;
+        self
+    }
+
+    /// Formats the duration.
+    pub fn format(&self, duration: Duration) -> String {
         let nanos = duration.as_nanos();
-        if nanos > NANOS_PER_MILLI {
-            format!("{:.2}ms", nanos as f64 / NANOS_PER_MILLI as f64)
-        } else if nanos > NANOS_PER_MICRO {
-            format!("{:.2}µs", nanos as f64 / NANOS_PER_MICRO as f64)
+        if let Some(unit) = self.unit {
+            return self.format_in(nanos, unit);
+        }
+        if self.compound && nanos >= NANOS_PER_SEC {
+            return self.format_compound(nanos);
+        }
+        let units = DurationUnit::ALL
+            .into_iter()
+            .filter(|unit| *unit <= self.max_unit);
+        let mut unit = units
+            .clone()
+            .rfind(|unit| nanos >= unit.nanos())
+            .unwrap_or(DurationUnit::Nanos);
+        // round to the precision of the unit before printing, and move on to the next
+        // unit when that spills over, so that e.g. 999.999µs becomes `1.00ms` instead
+        // of `1000.00µs`
+        let mut rounded = self.round(nanos, unit);
+        for next in units.filter(move |next| *next > unit) {
+            if rounded < next.nanos() {
+                break;
+            }
+            unit = next;
+            rounded = self.round(nanos, unit);
+        }
+        self.format_in(rounded, unit)
+    }
+
+    /// Rounds `nanos` to the smallest step printed in `unit`.
+    fn round(&self, nanos: u128, unit: DurationUnit) -> u128 {
+        let step = if unit == DurationUnit::Nanos {
+            1
+        } else {
+            (unit.nanos() / 10u128.pow(self.precision as u32)).max(1)
+        };
+        (nanos + step / 2) / step * step
+    }
+
+    fn format_in(&self, nanos: u128, unit: DurationUnit) -> String {
+        let suffix = unit.suffix(self.ascii);
+        if unit == DurationUnit::Nanos {
+            format!("{nanos}{suffix}")
         } else {
-            format!("{}ns", nanos)
+            let value = nanos as f64 / unit.nanos() as f64;
+            format!("{value:.precision$}{suffix}", precision = self.precision)
         }
     }
+
+    fn format_compound(&self, nanos: u128) -> String {
+        // round to the precision of the seconds first, so that e.g. 59.999s never
+        // turns into `60.00s` after the minutes have been split off
+        let step = 10u128.pow(9 - self.precision as u32);
+        let mut rest = (nanos + step / 2) / step * step;
+        let mut parts = Vec::new();
+        for unit in [DurationUnit::Days, DurationUnit::Hours, DurationUnit::Mins] {
+            let count = rest / unit.nanos();
+            rest %= unit.nanos();
+            if count > 0 {
+                parts.push(format!("{count}{}", unit.suffix(self.ascii)));
+            }
+        }
+        if rest > 0 || parts.is_empty() {
+            parts.push(self.format_in(rest, DurationUnit::Secs));
+        }
+        parts.join(" ")
+    }
+}
+
+static DEFAULT_FORMAT: RwLock<DurationFormat> = RwLock::new(DurationFormat::new());
+
+/// Sets the format used by [`format_duration`] and therefore by every report of this crate.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::display::{default_format, set_default_format, DurationFormat};
+///
+/// set_default_format(DurationFormat::new().ascii(true).precision(1));
+/// assert_eq!(default_format(), DurationFormat::new().ascii(true).precision(1));
+/// ```
+#[inline]
+pub fn set_default_format(format: DurationFormat) {
+    *write_ignoring_poison(&DEFAULT_FORMAT) = format;
+}
+
+/// Returns the format used by [`format_duration`].
+#[inline]
+pub fn default_format() -> DurationFormat {
+    *read_ignoring_poison(&DEFAULT_FORMAT)
+}
+
+/// Formats the duration with the [`default_format`].
+#[inline]
+pub fn format_duration(duration: Duration) -> String {
+    default_format().format(duration)
+}
+
+/// A quantity processed during a timed section, reported as a rate per second.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::display::Throughput;
+/// use std::time::Duration;
+///
+/// let second = Duration::from_secs(1);
+/// assert_eq!(Throughput::Bytes(3 << 20).format_rate(second), "3.00 MiB/s");
+/// assert_eq!(Throughput::BytesDecimal(3_000_000).format_rate(second), "3.00 MB/s");
+/// assert_eq!(Throughput::Elements(1_500).format_rate(second / 2), "3.00 Kelem/s");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Throughput {
+    /// Bytes, scaled with binary units: `KiB/s`, `MiB/s`, ...
+    Bytes(u64),
+    /// Bytes, scaled with decimal units: `kB/s`, `MB/s`, ...
+    BytesDecimal(u64),
+    /// Items such as rows or messages, scaled with decimal units: `Kelem/s`, `Melem/s`, ...
+    Elements(u64),
+}
+
+impl Throughput {
+    /// Returns the quantity per second processed in `duration`.
+    #[inline]
+    pub fn per_sec(&self, duration: Duration) -> f64 {
+        let (Throughput::Bytes(n) | Throughput::BytesDecimal(n) | Throughput::Elements(n)) = *self;
+        n as f64 / duration.as_secs_f64()
+    }
+
+    /// Formats the rate per second with two decimals and the largest fitting unit,
+    /// e.g. `85.33 MiB/s`.
+    pub fn format_rate(&self, duration: Duration) -> String {
+        let (base, units): (f64, [&str; 5]) = match self {
+            Throughput::Bytes(_) => (1024., ["B/s", "KiB/s", "MiB/s", "GiB/s", "TiB/s"]),
+            Throughput::BytesDecimal(_) => (1000., ["B/s", "kB/s", "MB/s", "GB/s", "TB/s"]),
+            Throughput::Elements(_) => (
+                1000.,
+                ["elem/s", "Kelem/s", "Melem/s", "Gelem/s", "Telem/s"],
+            ),
+        };
+        let (rate, unit) = scale(self.per_sec(duration), base, &units);
+        format!("{rate:.2} {unit}")
+    }
+}
+
+/// Formats a number of bytes with binary units, e.g. `512 B` or `4.00 KiB`.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::display::format_bytes;
+///
+/// assert_eq!(format_bytes(512), "512 B");
+/// assert_eq!(format_bytes(3 << 20), "3.00 MiB");
+/// ```
+pub fn format_bytes(bytes: u64) -> String {
+    match scale(bytes as f64, 1024., &["B", "KiB", "MiB", "GiB", "TiB"]) {
+        (_, "B") => format!("{bytes} B"),
+        (value, unit) => format!("{value:.2} {unit}"),
+    }
+}
+
+/// Divides `value` by `base` until it is below `base` or the largest unit is reached.
+fn scale<'a>(mut value: f64, base: f64, units: &[&'a str]) -> (f64, &'a str) {
+    let mut unit = 0;
+    while value >= base && unit < units.len() - 1 {
+        value /= base;
+        unit += 1;
+    }
+    (value, units[unit])
+}
+
+/// Formats rows as a table with a left-aligned first column and right-aligned other columns.
+pub(crate) fn format_table<const N: usize>(rows: &[[String; N]]) -> String {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let mut line = format!("{:<width$}", row[0], width = widths[0]);
+            for (cell, width) in row.iter().zip(widths).skip(1) {
+                line.push_str(&format!("  {cell:>width$}"));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_format() {
+        let format = DurationFormat::new();
+        assert_eq!(format.format(Duration::ZERO), "0ns");
+        assert_eq!(format.format(Duration::from_nanos(999)), "999ns");
+        assert_eq!(format.format(Duration::from_nanos(1_000)), "1.00µs");
+        assert_eq!(format.format(Duration::from_nanos(12_345)), "12.35µs");
+        assert_eq!(format.format(Duration::from_millis(1)), "1.00ms");
+        assert_eq!(format.format(Duration::from_micros(12_340)), "12.34ms");
+        assert_eq!(format.format(Duration::from_secs(1)), "1.00s");
+        assert_eq!(format.format(Duration::from_secs(3_600)), "3600.00s");
+        assert_eq!(format.format(Duration::from_nanos(999_994)), "999.99µs");
+        assert_eq!(format.format(Duration::from_nanos(999_995)), "1.00ms");
+        assert_eq!(format.format(Duration::from_nanos(999_999)), "1.00ms");
+        assert_eq!(format.format(Duration::from_nanos(999_996_000)), "1.00s");
+    }
+
+    #[test]
+    fn test_format_options() {
+        let duration = Duration::from_micros(1_500);
+        assert_eq!(DurationFormat::new().precision(0).format(duration), "2ms");
+        assert_eq!(
+            DurationFormat::new()
+                .precision(0)
+                .format(Duration::from_nanos(999_500)),
+            "1ms"
+        );
+        assert_eq!(
+            DurationFormat::new()
+                .precision(0)
+                .format(Duration::from_nanos(999_499)),
+            "999µs"
+        );
+        assert_eq!(
+            DurationFormat::new().precision(3).format(duration),
+            "1.500ms"
+        );
+        assert_eq!(
+            DurationFormat::new()
+                .unit(DurationUnit::Micros)
+                .ascii(true)
+                .format(duration),
+            "1500.00us"
+        );
+        assert_eq!(
+            DurationFormat::new()
+                .unit(DurationUnit::Nanos)
+                .format(duration),
+            "1500000ns"
+        );
+        let format = DurationFormat::new().max_unit(DurationUnit::Days);
+        assert_eq!(format.format(Duration::from_secs(90)), "1.50m");
+        assert_eq!(format.format(Duration::from_secs(9_000)), "2.50h");
+        assert_eq!(format.format(Duration::from_secs(129_600)), "1.50d");
+        assert_eq!(format.format(Duration::from_millis(59_999)), "1.00m");
+    }
+
+    #[test]
+    fn test_throughput() {
+        let second = Duration::from_secs(1);
+        assert_eq!(Throughput::Bytes(1023).format_rate(second), "1023.00 B/s");
+        assert_eq!(Throughput::Bytes(1024).format_rate(second), "1.00 KiB/s");
+        assert_eq!(
+            Throughput::Bytes(100 << 20).format_rate(Duration::from_millis(1_200)),
+            "83.33 MiB/s"
+        );
+        assert_eq!(
+            Throughput::Bytes(u64::MAX).format_rate(second),
+            "16777216.00 TiB/s"
+        );
+        assert_eq!(
+            Throughput::BytesDecimal(999).format_rate(second),
+            "999.00 B/s"
+        );
+        assert_eq!(
+            Throughput::BytesDecimal(2_500_000_000).format_rate(second),
+            "2.50 GB/s"
+        );
+        assert_eq!(
+            Throughput::Elements(42).format_rate(second * 2),
+            "21.00 elem/s"
+        );
+        assert_eq!(
+            Throughput::Elements(7_800_000).format_rate(second),
+            "7.80 Melem/s"
+        );
+        assert_eq!(
+            Throughput::Elements(10).per_sec(Duration::from_millis(100)),
+            100.
+        );
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.50 KiB");
+        assert_eq!(format_bytes(5 << 30), "5.00 GiB");
+    }
+
+    #[test]
+    fn test_compound_format() {
+        let format = DurationFormat::new().compound(true);
+        assert_eq!(format.format(Duration::from_millis(12)), "12.00ms");
+        assert_eq!(format.format(Duration::from_millis(3_500)), "3.50s");
+        assert_eq!(
+            format.format(Duration::from_millis(3_723_500)),
+            "1h 2m 3.50s"
+        );
+        assert_eq!(format.format(Duration::from_secs(90_000)), "1d 1h");
+        assert_eq!(format.format(Duration::from_millis(59_999)), "1m");
+        assert_eq!(
+            format.precision(1).format(Duration::from_millis(83_420)),
+            "1m 23.4s"
+        );
+    }
 }
@@ -0,0 +1,264 @@
+use crate::sink::{self, Sink};
+use crate::{display, registry, Clock, InstantClock};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// The timing of a future measured by [`TimedFuture`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PollTiming {
+    /// The wall time from the first poll to completion.
+    pub wall: Duration,
+    /// The total time spent inside `poll`.
+    pub busy: Duration,
+    /// The number of times the future was polled.
+    pub polls: u64,
+}
+
+impl PollTiming {
+    /// Returns the time the future spent waiting between polls.
+    #[inline]
+    pub fn idle(&self) -> Duration {
+        self.wall.saturating_sub(self.busy)
+    }
+}
+
+impl fmt::Display for PollTiming {
+    /// Formats the timing as e.g. `12.00ms (busy 1.20ms in 3 polls, idle 10.80ms)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (busy {} in {} poll{}, idle {})",
+            display::format_duration(self.wall),
+            display::format_duration(self.busy),
+            self.polls,
+            if self.polls == 1 { "" } else { "s" },
+            display::format_duration(self.idle())
+        )
+    }
+}
+
+/// A future which measures the wall time from its first poll to completion, the time
+/// spent inside `poll` and the number of polls of the wrapped future.
+///
+/// A high busy time means the future blocks the executor, a high idle time means it waits.
+/// On completion the timing is printed to the sink, or recorded into the [`registry`]
+/// when it is recording, and the future resolves to the output together with the timing.
+/// Nothing is reported if the future is dropped before completion.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::TimedFuture;
+///
+/// async fn handler() -> u32 {
+///     let (result, timing) = TimedFuture::new(async { 42 }, "fetch").await;
+///     // This will print "fetch took ... (busy ... in 1 poll, idle ...)"
+///     assert_eq!(timing.polls, 1);
+///     result
+/// }
+/// ```
+pub struct TimedFuture<F, C: Clock = InstantClock> {
+    future: Pin<Box<F>>,
+    clock: C,
+    task_name: String,
+    first_poll: Option<C::Instant>,
+    busy: Duration,
+    polls: u64,
+    sink: Option<Arc<dyn Sink>>,
+}
+
+impl<F: Future> TimedFuture<F> {
+    /// Wraps `future` to time it under `task_name`.
+    #[inline]
+    pub fn new(future: F, task_name: &str) -> Self {
+        Self::with_clock(future, task_name, InstantClock)
+    }
+}
+
+impl<F: Future, C: Clock> TimedFuture<F, C> {
+    /// Wraps `future` to time it under `task_name`, reading time from the given clock.
+    #[inline]
+    pub fn with_clock(future: F, task_name: &str, clock: C) -> Self {
+        TimedFuture {
+            future: Box::pin(future),
+            clock,
+            task_name: task_name.to_string(),
+            first_poll: None,
+            busy: Duration::ZERO,
+            polls: 0,
+            sink: None,
+        }
+    }
+
+    /// Sets the sink the report is written to, instead of the global sink.
+    #[inline]
+    pub fn set_sink(&mut self, sink: impl Sink + 'static) {
+        self.sink = Some(Arc::new(sink));
+    }
+
+    /// Returns the task name of the future.
+    #[inline]
+    pub fn task_name(&self) -> &str {
+        &self.task_name
+    }
+
+    /// Returns the timing measured so far.
+    #[inline]
+    pub fn timing(&self) -> PollTiming {
+        PollTiming {
+            wall: self
+                .first_poll
+                .map_or(Duration::ZERO, |first_poll| self.clock.elapsed(first_poll)),
+            busy: self.busy,
+            polls: self.polls,
+        }
+    }
+
+    fn report(&self, timing: PollTiming) {
+        if registry::is_recording() {
+            registry::record(&self.task_name, timing.wall);
+        } else {
+            let message = format!("{} took {}", self.task_name, timing);
+            sink::emit(self.sink.as_deref(), &message);
+        }
+    }
+}
+
+// the wrapped future is boxed, so the wrapper itself never needs to be pinned
+impl<F, C: Clock> Unpin for TimedFuture<F, C> {}
+
+impl<F: Future, C: Clock> Future for TimedFuture<F, C> {
+    type Output = (F::Output, PollTiming);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let start = this.clock.now();
+        let first_poll = *this.first_poll.get_or_insert(start);
+        let poll = this.future.as_mut().poll(cx);
+        let end = this.clock.now();
+        this.busy += this.clock.duration_between(start, end);
+        this.polls += 1;
+        match poll {
+            Poll::Ready(output) => {
+                let timing = PollTiming {
+                    wall: this.clock.duration_between(first_poll, end),
+                    busy: this.busy,
+                    polls: this.polls,
+                };
+                this.report(timing);
+                Poll::Ready((output, timing))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Awaits `future` and prints its wall time, poll time and number of polls,
+/// see [`TimedFuture`].
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::took_async;
+///
+/// async fn handler() -> u32 {
+///     let result = took_async(async { 1 + 1 }, "compute").await;
+///     // This will print "compute took ... (busy ... in 1 poll, idle ...)"
+///     result * 21
+/// }
+/// ```
+#[inline]
+pub async fn took_async<F: Future>(future: F, task_name: &str) -> F::Output {
+    TimedFuture::new(future, task_name).await.0
+}
+
+/// Awaits `future`, prints its timing and returns the output together with the timing.
+#[inline]
+pub async fn took_async_with_timing<F: Future>(
+    future: F,
+    task_name: &str,
+) -> (F::Output, PollTiming) {
+    TimedFuture::new(future, task_name).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::BufferSink;
+    use crate::ManualClock;
+
+    /// A future which is pending `remaining` times, advancing the clock by `work` on each poll.
+    struct Steps {
+        clock: ManualClock,
+        remaining: u32,
+        work: Duration,
+    }
+
+    impl Future for Steps {
+        type Output = &'static str;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<&'static str> {
+            self.clock.advance(self.work);
+            if self.remaining == 0 {
+                return Poll::Ready("done");
+            }
+            self.remaining -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn test_timed_future() {
+        let buffer = BufferSink::new();
+        let clock = ManualClock::new();
+        let steps = Steps {
+            clock: clock.clone(),
+            remaining: 2,
+            work: Duration::from_millis(1),
+        };
+        let mut future = TimedFuture::with_clock(steps, "steps", clock.clone());
+        future.set_sink(buffer.clone());
+        // time before the first poll is not counted
+        clock.advance(Duration::from_secs(1));
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        clock.advance(Duration::from_millis(10));
+        assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+        assert_eq!(future.timing().polls, 2);
+        clock.advance(Duration::from_millis(10));
+        let Poll::Ready((output, timing)) = Pin::new(&mut future).poll(&mut cx) else {
+            panic!("future should be ready");
+        };
+        assert_eq!(output, "done");
+        assert_eq!(
+            timing,
+            PollTiming {
+                wall: Duration::from_millis(23),
+                busy: Duration::from_millis(3),
+                polls: 3,
+            }
+        );
+        assert_eq!(timing.idle(), Duration::from_millis(20));
+        assert_eq!(
+            buffer.messages(),
+            ["steps took 23.00ms (busy 3.00ms in 3 polls, idle 20.00ms)"]
+        );
+    }
+
+    #[test]
+    fn test_took_async() {
+        let mut future = std::pin::pin!(took_async_with_timing(async { 42 }, "ready"));
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        let Poll::Ready((output, timing)) = future.as_mut().poll(&mut cx) else {
+            panic!("future should be ready");
+        };
+        assert_eq!(output, 42);
+        assert_eq!(timing.polls, 1);
+        assert!(timing.busy <= timing.wall);
+    }
+}
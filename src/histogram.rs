@@ -0,0 +1,319 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::display;
+
+/// Number of bits used to distinguish values within a power of two.
+///
+/// Values are bucketed with a relative error of at most `1 / 2^(SUB_BUCKET_BITS - 1)`, i.e. below 0.8%.
+const SUB_BUCKET_BITS: u32 = 8;
+const SUB_BUCKET_HALF: u64 = 1 << (SUB_BUCKET_BITS - 1);
+
+/// A latency histogram with log-linear buckets.
+///
+/// Durations below 256ns are counted exactly, larger durations fall into buckets
+/// whose width is proportional to their value, so every query has a bounded
+/// relative error (HDR-style) while the memory stays small.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::Histogram;
+/// use std::time::Duration;
+///
+/// let mut histogram = Histogram::new();
+/// for ms in 1..=100 {
+///     histogram.record(Duration::from_millis(ms));
+/// }
+/// let p99 = histogram.p99().as_secs_f64();
+/// assert!((p99 - 0.099).abs() / 0.099 < 0.01);
+/// println!("{histogram}"); // prints the distribution
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    min: u64,
+    max: u64,
+}
+
+/// Returns the index of the bucket containing `value` nanoseconds.
+#[inline]
+fn bucket_index(value: u64) -> usize {
+    if value < 2 * SUB_BUCKET_HALF {
+        return value as usize;
+    }
+    let msb = 63 - value.leading_zeros();
+    let shift = msb + 1 - SUB_BUCKET_BITS;
+    (shift as u64 * SUB_BUCKET_HALF + (value >> shift)) as usize
+}
+
+/// Returns the smallest and largest value, in nanoseconds, of the bucket at `index`.
+#[inline]
+fn bucket_range(index: usize) -> (u64, u64) {
+    let index = index as u64;
+    if index < 2 * SUB_BUCKET_HALF {
+        return (index, index);
+    }
+    let shift = index / SUB_BUCKET_HALF - 1;
+    let mantissa = index - shift * SUB_BUCKET_HALF;
+    let low = mantissa << shift;
+    (low, low + ((1u64 << shift) - 1))
+}
+
+impl Histogram {
+    /// Creates an empty histogram.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a duration to the histogram.
+    #[inline]
+    pub fn record(&mut self, duration: Duration) {
+        self.record_n(duration, 1);
+    }
+
+    /// Adds a duration to the histogram `n` times.
+    pub fn record_n(&mut self, duration: Duration, n: u64) {
+        if n == 0 {
+            return;
+        }
+        let value = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let index = bucket_index(value);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += n;
+        if self.count == 0 || value < self.min {
+            self.min = value;
+        }
+        self.max = self.max.max(value);
+        self.count += n;
+    }
+
+    /// Adds all durations recorded in `other` to this histogram.
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.count == 0 {
+            return;
+        }
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        if self.count == 0 || other.min < self.min {
+            self.min = other.min;
+        }
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+    }
+
+    /// Returns the number of recorded durations.
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns `true` if nothing has been recorded.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the shortest recorded duration.
+    #[inline]
+    pub fn min(&self) -> Duration {
+        Duration::from_nanos(self.min)
+    }
+
+    /// Returns the longest recorded duration.
+    #[inline]
+    pub fn max(&self) -> Duration {
+        Duration::from_nanos(self.max)
+    }
+
+    /// Returns the duration below which `percentile` percent of the recorded durations fall.
+    ///
+    /// `percentile` is clamped to `0..=100`. Returns zero if the histogram is empty.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((percentile.clamp(0., 100.) / 100. * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                if index == bucket_index(self.min) {
+                    return self.min();
+                }
+                let (_, high) = bucket_range(index);
+                return Duration::from_nanos(high.min(self.max));
+            }
+        }
+        self.max()
+    }
+
+    /// Returns the median.
+    #[inline]
+    pub fn p50(&self) -> Duration {
+        self.percentile(50.)
+    }
+
+    /// Returns the 90th percentile.
+    #[inline]
+    pub fn p90(&self) -> Duration {
+        self.percentile(90.)
+    }
+
+    /// Returns the 99th percentile.
+    #[inline]
+    pub fn p99(&self) -> Duration {
+        self.percentile(99.)
+    }
+
+    /// Returns the 99.9th percentile.
+    #[inline]
+    pub fn p999(&self) -> Duration {
+        self.percentile(99.9)
+    }
+
+    /// Returns the counts of the distribution grouped by powers of two,
+    /// as `(lower bound, upper bound, count)` for every non-empty group.
+    fn octaves(&self) -> Vec<(Duration, Duration, u64)> {
+        let mut octaves: Vec<(u64, u64, u64)> = Vec::new();
+        for (index, &count) in self.counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let (low, _) = bucket_range(index);
+            let octave = 64 - low.leading_zeros();
+            let octave_low = if octave == 0 { 0 } else { 1u64 << (octave - 1) };
+            let octave_high = if octave == 0 {
+                0
+            } else {
+                octave_low.saturating_mul(2)
+            };
+            match octaves.last_mut() {
+                Some(last) if last.0 == octave_low => last.2 += count,
+                _ => octaves.push((octave_low, octave_high, count)),
+            }
+        }
+        octaves
+            .into_iter()
+            .map(|(low, high, count)| {
+                (Duration::from_nanos(low), Duration::from_nanos(high), count)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Histogram {
+    /// Renders the distribution with one row per power of two.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const BAR_WIDTH: u64 = 40;
+        let octaves = self.octaves();
+        let max_count = octaves.iter().map(|o| o.2).max().unwrap_or(0);
+        let mut rows = vec![["range", "count", ""].map(String::from)];
+        for (low, high, count) in octaves {
+            let bar_len = (count * BAR_WIDTH).div_ceil(max_count) as usize;
+            rows.push([
+                format!(
+                    "{} .. {}",
+                    display::format_duration(low),
+                    display::format_duration(high)
+                ),
+                count.to_string(),
+                format!(
+                    "{:<width$}",
+                    "#".repeat(bar_len),
+                    width = BAR_WIDTH as usize
+                ),
+            ]);
+        }
+        let table = display::format_table(&rows);
+        let lines: Vec<_> = table.lines().map(str::trim_end).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_bounds() {
+        let mut last_high = None;
+        for index in 0..bucket_index(u64::MAX) + 1 {
+            let (low, high) = bucket_range(index);
+            assert!(low <= high);
+            if let Some(last_high) = last_high {
+                assert_eq!(low, last_high + 1);
+            }
+            assert_eq!(bucket_index(low), index);
+            assert_eq!(bucket_index(high), index);
+            assert!((high - low) as f64 <= low as f64 / SUB_BUCKET_HALF as f64);
+            last_high = Some(high);
+        }
+        assert_eq!(last_high, Some(u64::MAX));
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.p50(), Duration::ZERO);
+        for us in 1..=1000 {
+            histogram.record(Duration::from_micros(us));
+        }
+        assert_eq!(histogram.count(), 1000);
+        assert_eq!(histogram.min(), Duration::from_micros(1));
+        assert_eq!(histogram.max(), Duration::from_micros(1000));
+        assert_eq!(histogram.percentile(100.), histogram.max());
+        assert_eq!(histogram.percentile(0.), histogram.min());
+        for (value, expected) in [
+            (histogram.p50(), 500.),
+            (histogram.p90(), 900.),
+            (histogram.p99(), 990.),
+            (histogram.p999(), 999.),
+        ] {
+            let relative_error = (value.as_secs_f64() * 1e6 - expected).abs() / expected;
+            assert!(relative_error < 0.008, "{value:?} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = Histogram::new();
+        let mut b = Histogram::new();
+        a.record_n(Duration::from_millis(1), 3);
+        b.record(Duration::from_nanos(10));
+        b.record(Duration::from_secs(2));
+        a.merge(&b);
+        assert_eq!(a.count(), 5);
+        assert_eq!(a.min(), Duration::from_nanos(10));
+        assert_eq!(a.max(), Duration::from_secs(2));
+        assert!(a.p50() >= Duration::from_millis(1));
+        assert!(a.p50() < Duration::from_micros(1008));
+        let mut empty = Histogram::new();
+        empty.merge(&a);
+        assert_eq!(empty, a);
+    }
+
+    #[test]
+    fn test_display() {
+        let mut histogram = Histogram::new();
+        histogram.record_n(Duration::from_nanos(100), 4);
+        histogram.record_n(Duration::from_nanos(300), 2);
+        assert_eq!(
+            histogram.to_string(),
+            [
+                "range           count",
+                "64ns .. 128ns       4  ########################################",
+                "256ns .. 512ns      2  ####################",
+            ]
+            .join("\n")
+        );
+    }
+}
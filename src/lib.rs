@@ -6,9 +6,29 @@
 //!
 //! - Create named timers
 //! - Measure elapsed time
-//! - Format durations in a human-readable format
+//! - Format durations in a human-readable, configurable format and parse them back
 //! - Restart timers with new task names
-//! - Optional logging support using the `log` crate
+//! - Pause and resume timers to exclude idle time
+//! - Record named laps and print a per-lap breakdown
+//! - Pluggable clocks, including a `ManualClock` for deterministic tests
+//! - Scope guards which report on drop, even on early return or panic
+//! - Nested timing trees with self time and percentage of parent
+//! - A global registry aggregating repeated measurements by name
+//! - Latency histograms with percentile queries
+//! - Pluggable output sinks: stdout, stderr, any writer, memory or a callback
+//! - Slow-operation thresholds which only report calls over budget
+//! - Future timing with wall time, poll time and number of polls
+//! - Chrome Trace Event Format export for `chrome://tracing` and Perfetto
+//! - Folded-stack output of nested timings for flamegraph tools
+//! - A benchmark harness with warmup, robust statistics and outlier detection
+//! - Baselines saved to a file and compared with Welch's t-test to flag regressions
+//! - Throughput rates in bytes or elements per second next to durations
+//! - Optional CPU time measurement on Linux, user and system time next to wall time
+//! - Allocation counts of timed sections with an opt-in counting global allocator
+//! - Structured `TimingRecord`s with optional `serde` support
+//! - Optional logging support using the `log` crate, with configurable level and target
+//! - Optional `tracing` integration with structured events and spans
+//! - Optional `#[timed]` attribute for functions
 //!
 //! ## Installation
 //!
@@ -59,19 +79,93 @@
 //! timer.stop();
 //! ```
 //!
+//! ### Scope Guard Usage
+//! ```rust
+//! use tea_timer::Timer;
+//!
+//! fn parse(input: &str) -> Result<i32, std::num::ParseIntError> {
+//!     let _guard = Timer::scoped("parse");
+//!     let value = input.parse::<i32>()?;
+//!     Ok(value)
+//! } // this will print elapsed time when the guard is dropped, even on early return
+//! ```
+//!
 //! ### Logging Usage
 //! ```rust
+//! # #[cfg(feature = "log")]
+//! # {
 //! use tea_timer::Timer;
 //! use std::thread::sleep;
 //! use std::time::Duration;
 //!
 //! let mut timer = Timer::new("task");
 //! timer.log();  // This will log the elapsed time using the log crate
+//! timer.set_log_target("timing");
+//! timer.log_at(log::Level::Debug);  // This will log at debug level to the `timing` target
+//!
+//! // log a block at debug level to the `timing` target
+//! let result = tea_timer::ltook!(@level = debug, target = "timing"; 1 + 1);
+//! # }
 //! ```
 
-mod display;
+pub mod allocation;
+pub mod baseline;
+pub mod bench;
+pub mod chrome;
+mod clock;
+#[cfg(all(feature = "cpu-time", target_os = "linux"))]
+mod cpu;
+pub mod display;
+mod future;
+mod histogram;
+mod parse;
+pub mod record;
+pub mod registry;
+mod scope;
+pub mod sink;
+pub mod tree;
+
+pub use clock::{Clock, InstantClock, ManualClock};
+#[cfg(all(feature = "cpu-time", target_os = "linux"))]
+pub use cpu::{CpuTime, ProcessCpuClock, ThreadCpuClock};
+pub use display::{DurationFormat, DurationUnit, Throughput};
+pub use future::{took_async, took_async_with_timing, PollTiming, TimedFuture};
+pub use histogram::Histogram;
+pub use parse::{parse_duration, ParseDurationError};
+pub use record::TimingRecord;
+pub use scope::ScopedTimer;
+pub use sink::Sink;
+#[cfg(feature = "macros")]
+pub use tea_timer_macros::timed;
+
+// lets the code generated by `#[timed]` refer to `::tea_timer` inside this crate
+#[cfg(feature = "macros")]
+extern crate self as tea_timer;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, SystemTime};
+
+use allocation::{AllocSection, AllocStats};
+
+/// Locks `mutex`, ignoring poisoning: the data behind the locks of this crate stays
+/// consistent even if a thread panicked while holding the lock.
+#[inline]
+pub(crate) fn lock_ignoring_poison<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Locks `lock` for reading, ignoring poisoning, see [`lock_ignoring_poison`].
+#[inline]
+pub(crate) fn read_ignoring_poison<T: ?Sized>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
 
-use std::time::Instant;
+/// Locks `lock` for writing, ignoring poisoning, see [`lock_ignoring_poison`].
+#[inline]
+pub(crate) fn write_ignoring_poison<T: ?Sized>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
 
 /// A struct for measuring and reporting the duration of tasks.
 ///
@@ -86,9 +180,50 @@ use std::time::Instant;
 /// sleep(Duration::from_millis(100));
 /// timer.stop(); // This will print the duration of the task
 /// ```
-pub struct Timer {
-    pub start_time: Instant,
+pub struct Timer<C: Clock = InstantClock> {
+    pub start_time: C::Instant,
     pub task_name: String,
+    clock: C,
+    /// Active time accumulated by segments that have already been paused.
+    active: Duration,
+    /// Start of the current active segment, `None` while the timer is paused.
+    resumed_at: Option<C::Instant>,
+    laps: Vec<Lap>,
+    /// Wall-clock time of the start, for [`TimingRecord::start`].
+    started_at: SystemTime,
+    metadata: BTreeMap<String, String>,
+    /// Destination of the reports, the global sink is used if `None`.
+    sink: Option<Arc<dyn Sink>>,
+    /// Durations below the threshold are not reported, durations above it are escalated.
+    threshold: Option<Duration>,
+    /// Quantity processed by the task, reported as a rate.
+    throughput: Option<Throughput>,
+    /// Process CPU time when the timer started, if CPU time is measured.
+    #[cfg(all(feature = "cpu-time", target_os = "linux"))]
+    cpu_start: Option<CpuTime>,
+    /// Allocation counts at the start of the timer, if allocations are tracked.
+    alloc: Option<AllocSection>,
+    #[cfg(feature = "log")]
+    log_level: log::Level,
+    /// Target of the log records, the default target of the `log` crate is used if `None`.
+    #[cfg(feature = "log")]
+    log_target: Option<String>,
+    /// Whether a [`ScopedTimer`] created from this timer opens a `tracing` span.
+    #[cfg(feature = "tracing")]
+    trace_span: bool,
+}
+
+/// A named split recorded by [`Timer::lap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lap {
+    pub name: String,
+    /// Active time since the previous lap (or since the timer started).
+    #[cfg_attr(feature = "serde", serde(with = "record::serde_duration::nanos"))]
+    pub split: Duration,
+    /// Active time since the timer started.
+    #[cfg_attr(feature = "serde", serde(with = "record::serde_duration::nanos"))]
+    pub total: Duration,
 }
 
 impl Default for Timer {
@@ -98,14 +233,14 @@ impl Default for Timer {
     }
 }
 
-impl std::fmt::Debug for Timer {
+impl<C: Clock> std::fmt::Debug for Timer<C> {
     #[inline]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.elapsed_str())
     }
 }
 
-impl std::fmt::Display for Timer {
+impl<C: Clock> std::fmt::Display for Timer<C> {
     #[inline]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.elapsed_str())
@@ -125,12 +260,259 @@ impl Timer {
     /// ```
     #[inline]
     pub fn new(task_name: &str) -> Self {
+        Timer::with_clock(task_name, InstantClock)
+    }
+}
+
+impl<C: Clock> Timer<C> {
+    /// Creates a new `Timer` instance which reads time from the given clock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tea_timer::{ManualClock, Timer};
+    /// use std::time::Duration;
+    ///
+    /// let clock = ManualClock::new();
+    /// let timer = Timer::with_clock("My Task", clock.clone());
+    /// clock.advance(Duration::from_secs(2));
+    /// assert_eq!(timer.duration_str(), "2.00s");
+    /// ```
+    #[inline]
+    pub fn with_clock(task_name: &str, clock: C) -> Self {
+        let now = clock.now();
         Timer {
-            start_time: Instant::now(),
+            start_time: now,
             task_name: task_name.to_string(),
+            clock,
+            active: Duration::ZERO,
+            resumed_at: Some(now),
+            laps: Vec::new(),
+            started_at: SystemTime::now(),
+            metadata: BTreeMap::new(),
+            sink: None,
+            threshold: None,
+            throughput: None,
+            #[cfg(all(feature = "cpu-time", target_os = "linux"))]
+            cpu_start: None,
+            alloc: None,
+            #[cfg(feature = "log")]
+            log_level: log::Level::Info,
+            #[cfg(feature = "log")]
+            log_target: None,
+            #[cfg(feature = "tracing")]
+            trace_span: false,
+        }
+    }
+
+    /// Returns the clock used by the timer.
+    #[inline]
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Sets the destination of the reports of this timer, overriding the global
+    /// sink set with [`sink::set_global`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tea_timer::{sink::BufferSink, ManualClock, Timer};
+    /// use std::time::Duration;
+    ///
+    /// let buffer = BufferSink::new();
+    /// let clock = ManualClock::new();
+    /// let mut timer = Timer::with_clock("Task", clock.clone());
+    /// timer.set_sink(buffer.clone());
+    /// clock.advance(Duration::from_millis(5));
+    /// timer.stop();
+    /// assert_eq!(buffer.messages(), ["Task took 5.00ms"]);
+    /// ```
+    #[inline]
+    pub fn set_sink(&mut self, sink: impl Sink + 'static) {
+        self.sink = Some(Arc::new(sink));
+    }
+
+    /// Sets a threshold below which the final report of the timer is skipped.
+    ///
+    /// When the threshold is exceeded the report is escalated: the message says by how much
+    /// the budget was overrun, and reports through the `log` crate are logged at least at
+    /// `Warn` level.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tea_timer::Timer;
+    /// use std::time::Duration;
+    ///
+    /// let mut timer = Timer::new("Fast Task");
+    /// timer.set_threshold(Duration::from_secs(1));
+    /// timer.stop(); // This will print "Fast Task took 1.20s (over budget of 1.00s by 200.00ms)"
+    ///               // if the task took 1.2 seconds, and nothing if it took less than a second
+    /// ```
+    #[inline]
+    pub fn set_threshold(&mut self, threshold: Duration) {
+        self.threshold = Some(threshold);
+    }
+
+    /// Returns the threshold set with [`Timer::set_threshold`], if any.
+    #[inline]
+    pub fn threshold(&self) -> Option<Duration> {
+        self.threshold
+    }
+
+    /// Returns `true` if `duration` reaches the threshold of the timer, if any.
+    #[inline]
+    fn should_report(&self, duration: Duration) -> bool {
+        self.threshold.is_none_or(|threshold| duration >= threshold)
+    }
+
+    /// Returns by how much `duration` overruns the threshold of the timer, or `None`
+    /// if there is no threshold or it is not exceeded.
+    #[inline]
+    fn overrun(&self, duration: Duration) -> Option<Duration> {
+        self.threshold
+            .and_then(|threshold| duration.checked_sub(threshold))
+            .filter(|overrun| !overrun.is_zero())
+    }
+
+    /// Returns ` (over budget of .. by ..)` if `duration` exceeds the threshold of the timer.
+    fn over_budget_str(&self, duration: Duration) -> String {
+        match (self.threshold, self.overrun(duration)) {
+            (Some(threshold), Some(overrun)) => format!(
+                " (over budget of {} by {})",
+                display::format_duration(threshold),
+                display::format_duration(overrun)
+            ),
+            _ => String::new(),
+        }
+    }
+
+    /// Sets the quantity processed by the task, so reports append the rate per second.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tea_timer::{ManualClock, Throughput, Timer};
+    /// use std::time::Duration;
+    ///
+    /// let clock = ManualClock::new();
+    /// let mut timer = Timer::with_clock("Parse", clock.clone());
+    /// timer.set_throughput(Throughput::Bytes(100 << 20));
+    /// clock.advance(Duration::from_millis(1_200));
+    /// assert_eq!(timer.took_str(), "Parse took 1.20s (83.33 MiB/s)");
+    /// ```
+    #[inline]
+    pub fn set_throughput(&mut self, throughput: Throughput) {
+        self.throughput = Some(throughput);
+    }
+
+    /// Returns the throughput set with [`Timer::set_throughput`], if any.
+    #[inline]
+    pub fn throughput(&self) -> Option<Throughput> {
+        self.throughput
+    }
+
+    /// Enables or disables measuring the CPU time of the process next to the duration,
+    /// starting now. Reports then show both, e.g. `Task took wall 2.10s, cpu 7.80s (3.7x)`,
+    /// which tells whether a parallel section actually keeps several cores busy.
+    ///
+    /// The CPU time is measured from this call or the last [`Timer::restart`], pauses
+    /// are not excluded.
+    ///
+    /// This method is only available on Linux when the `cpu-time` feature is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tea_timer::Timer;
+    ///
+    /// let mut timer = Timer::new("Sum");
+    /// timer.set_cpu_time(true);
+    /// let sum: u64 = (0..1_000_000u64).sum();
+    /// let cpu = timer.cpu_time().unwrap();
+    /// println!("user {:?}, system {:?}", cpu.user, cpu.system);
+    /// assert!(timer.took_str().starts_with("Sum took wall "));
+    /// # assert!(sum > 0);
+    /// ```
+    #[inline]
+    #[cfg(all(feature = "cpu-time", target_os = "linux"))]
+    pub fn set_cpu_time(&mut self, enabled: bool) {
+        self.cpu_start = enabled.then(CpuTime::process);
+    }
+
+    /// Returns the CPU time used by the process since [`Timer::set_cpu_time`] was enabled.
+    ///
+    /// This method is only available on Linux when the `cpu-time` feature is enabled.
+    #[inline]
+    #[cfg(all(feature = "cpu-time", target_os = "linux"))]
+    pub fn cpu_time(&self) -> Option<CpuTime> {
+        self.cpu_start
+            .map(|start| CpuTime::process().saturating_sub(start))
+    }
+
+    /// Enables or disables counting the allocations of the task, starting now. Reports then
+    /// show the number of allocations and deallocations, the bytes allocated and the peak
+    /// of bytes allocated at the same time, e.g.
+    /// `Task took 1.20ms (12 allocs, 10 deallocs, 4.00 KiB allocated, 2.00 KiB peak)`.
+    ///
+    /// Allocations are only counted if [`CountingAllocator`](allocation::CountingAllocator)
+    /// is the global allocator. The counts are process-wide, so allocations of other
+    /// threads running at the same time are included, in the peak as well.
+    #[inline]
+    pub fn set_alloc_tracking(&mut self, enabled: bool) {
+        self.alloc = None;
+        if enabled {
+            self.alloc = Some(AllocSection::start());
+        }
+    }
+
+    /// Returns the allocation counts since [`Timer::set_alloc_tracking`] was enabled.
+    #[inline]
+    pub fn alloc_stats(&self) -> Option<AllocStats> {
+        self.alloc.as_ref().map(AllocSection::stats)
+    }
+
+    /// Returns `duration` formatted for a report, with the CPU time, the allocation
+    /// counts and the rate if they are measured.
+    fn measurement_str(&self, duration: Duration) -> String {
+        #[allow(unused_mut)]
+        let mut measurement = display::format_duration(duration);
+        #[cfg(all(feature = "cpu-time", target_os = "linux"))]
+        if let Some(cpu) = self.cpu_time() {
+            measurement = format!(
+                "wall {measurement}, cpu {}",
+                display::format_duration(cpu.total())
+            );
+            if !duration.is_zero() {
+                let ratio = cpu.total().as_secs_f64() / duration.as_secs_f64();
+                measurement.push_str(&format!(" ({ratio:.1}x)"));
+            }
+        }
+        if let Some(stats) = self.alloc_stats() {
+            measurement.push_str(&format!(" ({stats})"));
+        }
+        measurement.push_str(&self.rate_str(duration));
+        measurement
+    }
+
+    /// Returns the rate of the throughput over `duration` as ` (85.33 MiB/s)`, or an empty
+    /// string if there is no throughput or no time has passed.
+    fn rate_str(&self, duration: Duration) -> String {
+        match self.throughput {
+            Some(throughput) if !duration.is_zero() => {
+                format!(" ({})", throughput.format_rate(duration))
+            }
+            _ => String::new(),
         }
     }
 
+    /// Writes a report to the sink of the timer.
+    #[inline]
+    fn emit(&self, message: &str) {
+        sink::emit(self.sink.as_deref(), message);
+    }
+
     /// Restarts the timer with a new task name.
     ///
     /// # Examples
@@ -146,11 +528,65 @@ impl Timer {
     /// ```
     #[inline]
     pub fn restart(&mut self, task_name: &str) {
-        self.start_time = Instant::now();
+        let now = self.clock.now();
+        self.start_time = now;
         self.task_name = task_name.to_string();
+        self.active = Duration::ZERO;
+        self.resumed_at = Some(now);
+        self.laps.clear();
+        self.started_at = SystemTime::now();
+        #[cfg(all(feature = "cpu-time", target_os = "linux"))]
+        if self.cpu_start.is_some() {
+            self.cpu_start = Some(CpuTime::process());
+        }
+        if self.alloc.is_some() {
+            // the previous section frees its peak slot before the new one starts
+            self.alloc = None;
+            self.alloc = Some(AllocSection::start());
+        }
+    }
+
+    /// Pauses the timer, time spent while paused is not counted by [`Timer::duration`].
+    ///
+    /// Pausing an already paused timer does nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tea_timer::Timer;
+    /// use std::thread::sleep;
+    /// use std::time::Duration;
+    ///
+    /// let mut timer = Timer::new("Task");
+    /// timer.pause();
+    /// sleep(Duration::from_millis(10));
+    /// timer.resume();
+    /// assert!(timer.duration() < timer.wall_duration());
+    /// ```
+    #[inline]
+    pub fn pause(&mut self) {
+        if let Some(resumed_at) = self.resumed_at.take() {
+            self.active += self.clock.elapsed(resumed_at);
+        }
+    }
+
+    /// Resumes a paused timer.
+    ///
+    /// Resuming a timer which is already running does nothing.
+    #[inline]
+    pub fn resume(&mut self) {
+        if self.resumed_at.is_none() {
+            self.resumed_at = Some(self.clock.now());
+        }
+    }
+
+    /// Returns `true` if the timer is currently paused.
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.resumed_at.is_none()
     }
 
-    /// Returns the duration elapsed since the timer started.
+    /// Returns the active duration of the task, time spent while paused is excluded.
     ///
     /// # Examples
     ///
@@ -164,8 +600,76 @@ impl Timer {
     /// assert!(timer.duration().as_millis() >= 10);
     /// ```
     #[inline]
-    pub fn duration(&self) -> std::time::Duration {
-        self.start_time.elapsed()
+    pub fn duration(&self) -> Duration {
+        match self.resumed_at {
+            Some(resumed_at) => self.active + self.clock.elapsed(resumed_at),
+            None => self.active,
+        }
+    }
+
+    /// Returns the wall-clock duration elapsed since the timer started, including paused time.
+    #[inline]
+    pub fn wall_duration(&self) -> Duration {
+        self.clock.elapsed(self.start_time)
+    }
+
+    /// Records a named split and returns it.
+    ///
+    /// The split is the active time since the previous lap, the total is the
+    /// active time since the timer started.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tea_timer::Timer;
+    ///
+    /// let mut timer = Timer::new("Pipeline");
+    /// // load data...
+    /// timer.lap("load");
+    /// // transform data...
+    /// let lap = timer.lap("transform");
+    /// assert_eq!(lap.name, "transform");
+    /// assert_eq!(timer.laps().len(), 2);
+    /// timer.stop(); // This will print the total duration and a table of laps
+    /// ```
+    pub fn lap(&mut self, name: &str) -> Lap {
+        let total = self.duration();
+        let last = self.laps.last().map_or(Duration::ZERO, |lap| lap.total);
+        let lap = Lap {
+            name: name.to_string(),
+            split: total.saturating_sub(last),
+            total,
+        };
+        self.laps.push(lap.clone());
+        lap
+    }
+
+    /// Returns the laps recorded so far.
+    #[inline]
+    pub fn laps(&self) -> &[Lap] {
+        &self.laps
+    }
+
+    /// Returns a table of the recorded laps, one row per lap.
+    ///
+    /// Returns an empty string if no lap has been recorded.
+    pub fn laps_str(&self) -> String {
+        if self.laps.is_empty() {
+            return String::new();
+        }
+        let mut rows = vec![["lap", "split", "total"].map(String::from)];
+        rows.extend(self.laps.iter().map(|lap| {
+            [
+                lap.name.clone(),
+                display::format_duration(lap.split),
+                display::format_duration(lap.total),
+            ]
+        }));
+        display::format_table(&rows)
+            .lines()
+            .map(|line| format!("  {line}"))
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 
     /// Returns a formatted string representation of the elapsed duration.
@@ -186,17 +690,29 @@ impl Timer {
         display::format_duration(self.duration())
     }
 
+    /// Returns the elapsed duration with the task name, followed by the CPU time,
+    /// the allocation counts and the rate if they are measured.
     #[inline]
     pub fn elapsed_str(&self) -> String {
-        format!("{} elapsed {}", self.task_name, self.duration_str())
+        format!(
+            "{} elapsed {}",
+            self.task_name,
+            self.measurement_str(self.duration())
+        )
     }
 
+    /// Returns the duration of the task with the task name, followed by the CPU time,
+    /// the allocation counts and the rate if they are measured.
     #[inline]
     pub fn took_str(&self) -> String {
-        format!("{} took {}", self.task_name, self.duration_str())
+        format!(
+            "{} took {}",
+            self.task_name,
+            self.measurement_str(self.duration())
+        )
     }
 
-    /// Prints the elapsed time for the task.
+    /// Prints the elapsed time for the task to the [`sink`] of the timer.
     ///
     /// # Examples
     ///
@@ -211,10 +727,12 @@ impl Timer {
     /// ```
     #[inline]
     pub fn elapsed(&self) {
-        println!("{}", self.elapsed_str());
+        self.emit(&self.elapsed_str());
     }
 
-    /// Stops the timer and prints the duration of the task.
+    /// Stops the timer, prints the duration of the task and returns it.
+    ///
+    /// If the [`registry`] is recording, the duration is recorded silently instead.
     ///
     /// # Examples
     ///
@@ -225,14 +743,85 @@ impl Timer {
     ///
     /// let timer = Timer::new("Sleep Task");
     /// sleep(Duration::from_millis(100));
-    /// timer.stop(); // This will print: "Sleep Task took 100.00ms" (approximately)
+    /// let duration = timer.stop(); // This will print: "Sleep Task took 100.00ms" (approximately)
+    /// assert!(duration.as_millis() >= 100);
     /// ```
     #[inline]
-    pub fn stop(self) {
-        println!("{}", self.took_str());
+    pub fn stop(self) -> Duration {
+        self.report_took()
+    }
+
+    /// Stops the timer, prints the duration of the task like [`Timer::stop`] and returns
+    /// a [`TimingRecord`] of everything it measured.
+    #[inline]
+    pub fn stop_record(self) -> TimingRecord {
+        let duration = self.report_took();
+        self.record_with(duration)
+    }
+
+    /// Returns a [`TimingRecord`] of the timer so far, without reporting anything.
+    #[inline]
+    pub fn record(&self) -> TimingRecord {
+        self.record_with(self.duration())
+    }
+
+    fn record_with(&self, duration: Duration) -> TimingRecord {
+        TimingRecord {
+            name: self.task_name.clone(),
+            start: self.started_at,
+            duration,
+            thread: record::current_thread(),
+            laps: self.laps.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    /// Attaches a key-value pair to the [`TimingRecord`] of the timer, replacing
+    /// the previous value of the key.
+    #[inline]
+    pub fn set_metadata(&mut self, key: &str, value: impl ToString) {
+        self.metadata.insert(key.to_string(), value.to_string());
+    }
+
+    /// Returns the metadata set with [`Timer::set_metadata`].
+    #[inline]
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// Prints the duration of the task and the recorded laps to the sink, returning the duration.
+    ///
+    /// Nothing is printed if the [`registry`] is recording, the duration is recorded instead.
+    fn report_took(&self) -> Duration {
+        let duration = self.duration();
+        if registry::is_recording() {
+            registry::record(&self.task_name, duration);
+        } else if self.should_report(duration) {
+            self.emit(&self.took_message(duration));
+        }
+        duration
     }
 
-    /// Logs the elapsed time using the `log` crate.
+    /// Returns the final report of the timer for `duration`, followed by the table of laps.
+    fn took_message(&self, duration: Duration) -> String {
+        let mut message = format!(
+            "{} took {}{}",
+            self.task_name,
+            self.measurement_str(duration),
+            self.over_budget_str(duration)
+        );
+        if !self.laps.is_empty() {
+            message.push('\n');
+            message.push_str(&self.laps_str());
+        }
+        message
+    }
+
+    /// Logs the elapsed time using the `log` crate, at the level set with
+    /// [`Timer::set_log_level`] (`Info` by default).
+    ///
+    /// Like the final report, nothing is logged below the threshold of the timer, and an
+    /// elapsed time over it is logged at least at `Warn` level, see [`Timer::set_threshold`].
     ///
     /// This method is only available when the `log` feature is enabled.
     ///
@@ -253,60 +842,556 @@ impl Timer {
     #[inline]
     #[cfg(feature = "log")]
     pub fn log(&self) {
-        log::info!("{}", self.elapsed_str());
+        self.log_at(self.log_level);
     }
-}
 
-#[inline]
-pub fn took<F: FnOnce() -> R, R>(f: F, task_name: &str) -> R {
-    let timer = Timer::new(task_name);
-    let result = f();
-    timer.stop();
-    result
-}
+    /// Logs the elapsed time using the `log` crate at the given level, or at least at `Warn`
+    /// level if it exceeds the threshold of the timer. Nothing is logged below the threshold.
+    ///
+    /// This method is only available when the `log` feature is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "log")]
+    /// # {
+    /// use tea_timer::Timer;
+    ///
+    /// let mut timer = Timer::new("Log Task");
+    /// timer.set_log_target("timing");
+    /// timer.log_at(log::Level::Debug); // This will log to the `timing` target at debug level
+    /// # }
+    /// ```
+    #[inline]
+    #[cfg(feature = "log")]
+    pub fn log_at(&self, level: log::Level) {
+        let duration = self.duration();
+        if !self.should_report(duration) {
+            return;
+        }
+        let message = format!(
+            "{} elapsed {}{}",
+            self.task_name,
+            self.measurement_str(duration),
+            self.over_budget_str(duration)
+        );
+        self.log_message(self.escalated(level, duration), &message);
+    }
 
-#[inline]
-pub fn ltook<F: FnOnce() -> R, R>(f: F, task_name: &str) -> R {
-    let timer = Timer::new(task_name);
-    let result = f();
-    timer.log();
-    result
-}
+    /// Sets the level used by [`Timer::log`] and [`ltook!`], `Info` by default.
+    ///
+    /// This method is only available when the `log` feature is enabled.
+    #[inline]
+    #[cfg(feature = "log")]
+    pub fn set_log_level(&mut self, level: log::Level) {
+        self.log_level = level;
+    }
 
-#[macro_export]
-macro_rules! took {
-    ($($tt:tt)*) => {
-        {
-            let timer = $crate::Timer::new("");
-            let res = {$($tt)*};
-            timer.stop();
-            res
-        }
-    };
-}
+    /// Sets the target of the log records of this timer, so they can be routed
+    /// to a dedicated logger or filtered separately.
+    ///
+    /// This method is only available when the `log` feature is enabled.
+    #[inline]
+    #[cfg(feature = "log")]
+    pub fn set_log_target(&mut self, target: &str) {
+        self.log_target = Some(target.to_string());
+    }
 
-#[macro_export]
-macro_rules! ltook {
-    ($($tt:tt)*) => {
-        {
-            let timer = $crate::Timer::new("");
-            let res = {$($tt)*};
-            timer.log();
-            res
+    /// Returns `level`, raised to at least `Warn` if `duration` exceeds the threshold.
+    #[cfg(feature = "log")]
+    fn escalated(&self, level: log::Level, duration: Duration) -> log::Level {
+        match self.overrun(duration) {
+            Some(_) => level.min(log::Level::Warn),
+            None => level,
         }
-    };
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::thread::sleep;
-    use std::time::Duration;
+    }
 
-    #[test]
-    fn test_timer_new() {
-        let timer = Timer::new("Test Task");
-        assert_eq!(timer.task_name, "Test Task");
+    #[cfg(feature = "log")]
+    fn log_message(&self, level: log::Level, message: &str) {
+        match &self.log_target {
+            Some(target) => log::log!(target: target, level, "{message}"),
+            None => log::log!(level, "{message}"),
+        }
+    }
+
+    /// Emits a `tracing` event at `INFO` level with the fields `task`, `elapsed_ns`
+    /// and `status = "running"`.
+    ///
+    /// This method is only available when the `tracing` feature is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "tracing")]
+    /// # {
+    /// use tea_timer::Timer;
+    ///
+    /// let timer = Timer::new("Trace Task");
+    /// timer.trace(); // This will emit an event to the current tracing subscriber
+    /// # }
+    /// ```
+    #[inline]
+    #[cfg(feature = "tracing")]
+    pub fn trace(&self) {
+        self.trace_at(tracing::Level::INFO);
+    }
+
+    /// Emits a `tracing` event at the given level, see [`Timer::trace`].
+    ///
+    /// This method is only available when the `tracing` feature is enabled.
+    #[inline]
+    #[cfg(feature = "tracing")]
+    pub fn trace_at(&self, level: tracing::Level) {
+        self.trace_event(level, "running", self.duration(), &self.elapsed_str());
+    }
+
+    /// Makes a [`ScopedTimer`] created from this timer open and enter a `tracing` span
+    /// named `took` with a `task` field while it is alive, so events and timers inside
+    /// the scope become its children.
+    ///
+    /// This method is only available when the `tracing` feature is enabled.
+    #[inline]
+    #[cfg(feature = "tracing")]
+    pub fn set_trace_span(&mut self, enabled: bool) {
+        self.trace_span = enabled;
+    }
+
+    #[cfg(feature = "tracing")]
+    fn trace_event(&self, level: tracing::Level, status: &str, duration: Duration, message: &str) {
+        use tracing::Level;
+
+        macro_rules! event {
+            ($level:expr) => {
+                tracing::event!(
+                    $level,
+                    task = %self.task_name,
+                    elapsed_ns = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX),
+                    status,
+                    "{message}"
+                )
+            };
+        }
+        // the level of a tracing event must be a constant
+        match level {
+            Level::ERROR => event!(Level::ERROR),
+            Level::WARN => event!(Level::WARN),
+            Level::INFO => event!(Level::INFO),
+            Level::DEBUG => event!(Level::DEBUG),
+            _ => event!(Level::TRACE),
+        }
+    }
+}
+
+#[inline]
+pub fn took<F: FnOnce() -> R, R>(f: F, task_name: &str) -> R {
+    let timer = Timer::scoped(task_name);
+    let result = f();
+    timer.finish();
+    result
+}
+
+/// Runs `f` and prints the time it took, reading time from the given clock.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::{took_with_clock, ManualClock};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let result = took_with_clock(
+///     || {
+///         clock.advance(Duration::from_millis(5));
+///         42
+///     },
+///     "task",
+///     clock.clone(),
+/// );
+/// assert_eq!(result, 42);
+/// ```
+#[inline]
+pub fn took_with_clock<C: Clock, F: FnOnce() -> R, R>(f: F, task_name: &str, clock: C) -> R {
+    let timer = Timer::with_clock(task_name, clock).into_scoped();
+    let result = f();
+    timer.finish();
+    result
+}
+
+/// Runs `f` and prints the time it took only if it reached `threshold`, saying by how much
+/// the budget was overrun, see [`Timer::set_threshold`].
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::took_with_threshold;
+/// use std::time::Duration;
+///
+/// // This will print nothing unless parsing took at least 10ms
+/// let result = took_with_threshold(|| "42".parse::<u32>(), "parse", Duration::from_millis(10));
+/// assert_eq!(result, Ok(42));
+/// ```
+#[inline]
+pub fn took_with_threshold<F: FnOnce() -> R, R>(f: F, task_name: &str, threshold: Duration) -> R {
+    let mut timer = Timer::new(task_name);
+    timer.set_threshold(threshold);
+    let timer = timer.into_scoped();
+    let result = f();
+    timer.finish();
+    result
+}
+
+/// Runs `f` and prints the time it took, followed by the rate of the `throughput`
+/// processed by `f`.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::{took_with_throughput, Throughput};
+///
+/// let input = "1,2,3\n4,5,6\n";
+/// let rows = took_with_throughput(
+///     || input.lines().count(),
+///     "parse",
+///     Throughput::Bytes(input.len() as u64),
+/// ); // This will print e.g. "parse took 1.20µs (9.54 MiB/s)"
+/// assert_eq!(rows, 2);
+/// ```
+#[inline]
+pub fn took_with_throughput<F: FnOnce() -> R, R>(
+    f: F,
+    task_name: &str,
+    throughput: Throughput,
+) -> R {
+    let mut timer = Timer::new(task_name);
+    timer.set_throughput(throughput);
+    let timer = timer.into_scoped();
+    let result = f();
+    timer.finish();
+    result
+}
+
+/// Runs `f`, prints the time it took and returns the result together with a
+/// [`TimingRecord`] of the measurement.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::took_record;
+///
+/// let (result, record) = took_record(|| 1 + 1, "task");
+/// assert_eq!(result, 2);
+/// assert_eq!(record.name, "task");
+/// ```
+#[inline]
+pub fn took_record<F: FnOnce() -> R, R>(f: F, task_name: &str) -> (R, TimingRecord) {
+    let timer = Timer::scoped(task_name);
+    let result = f();
+    let record = timer.finish_record();
+    (result, record)
+}
+
+/// Runs `f`, prints the time it took and returns the result together with the duration.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::took_with_duration;
+///
+/// let (result, duration) = took_with_duration(|| 1 + 1, "task");
+/// assert_eq!(result, 2);
+/// assert!(duration.as_secs() < 1);
+/// ```
+#[inline]
+pub fn took_with_duration<F: FnOnce() -> R, R>(f: F, task_name: &str) -> (R, Duration) {
+    let timer = Timer::scoped(task_name);
+    let result = f();
+    let duration = timer.finish();
+    (result, duration)
+}
+
+#[inline]
+#[cfg(feature = "log")]
+pub fn ltook<F: FnOnce() -> R, R>(f: F, task_name: &str) -> R {
+    let timer = Timer::scoped(task_name);
+    let result = f();
+    timer.finish_log();
+    result
+}
+
+/// Runs `f` and logs the time it took only if it reached `threshold`, at least at `Warn`
+/// level if it exceeded it, see [`Timer::set_threshold`].
+///
+/// This function is only available when the `log` feature is enabled.
+#[inline]
+#[cfg(feature = "log")]
+pub fn ltook_with_threshold<F: FnOnce() -> R, R>(f: F, task_name: &str, threshold: Duration) -> R {
+    let mut timer = Timer::new(task_name);
+    timer.set_threshold(threshold);
+    let timer = timer.into_scoped();
+    let result = f();
+    timer.finish_log();
+    result
+}
+
+/// Runs `f` and emits a `tracing` event with the time it took, see [`Timer::trace`].
+///
+/// The closure runs inside a `took` span, so its own events and timers become children of the span.
+///
+/// This function is only available when the `tracing` feature is enabled.
+#[inline]
+#[cfg(feature = "tracing")]
+pub fn ttook<F: FnOnce() -> R, R>(f: F, task_name: &str) -> R {
+    let mut timer = Timer::new(task_name);
+    timer.set_trace_span(true);
+    let timer = timer.into_scoped();
+    let result = f();
+    timer.finish_trace();
+    result
+}
+
+/// Runs `f`, logs the time it took and returns the result together with the duration.
+#[inline]
+#[cfg(feature = "log")]
+pub fn ltook_with_duration<F: FnOnce() -> R, R>(f: F, task_name: &str) -> (R, Duration) {
+    let timer = Timer::scoped(task_name);
+    let result = f();
+    let duration = timer.finish_log();
+    (result, duration)
+}
+
+/// Times a block of code, prints the time it took and evaluates to the result of the block.
+///
+/// The task name defaults to the `file:line` of the invocation. A name can be given
+/// as a format string with arguments in front of the block, introduced by `@` and
+/// terminated by `;`: `took!(@"parse {}", path; ...)`.
+///
+/// Alternatively the block can be preceded by `@` and options separated by commas and
+/// terminated by `;`. As `@` cannot start a block, any block without it is timed as is.
+///
+/// - `name = "parse {path}"`: the task name, a string literal is used as a format string,
+///   any other expression is converted with `ToString`
+/// - `level = debug`: the level used by `ltook!`, one of `error`, `warn`, `info`,
+///   `debug`, `trace` or a `log::Level` expression (requires the `log` feature)
+/// - `target = "timing"`: the target of the log records of `ltook!` (requires the
+///   `log` feature)
+/// - `span = true`: open a `tracing` span named `took` for the duration of the block
+///   (requires the `tracing` feature)
+/// - `threshold = "10ms"`: only report if the block took at least the threshold, as a
+///   [`Duration`] expression or a string literal accepted by [`parse_duration`]. The
+///   literal is parsed when the block runs, which panics if it is not a valid duration
+/// - `throughput = Throughput::Bytes(len)`: report the rate of the quantity processed
+///   by the block, see [`Throughput`]
+/// - `allocations = true`: report the allocations of the block, see [`Timer::set_alloc_tracking`]
+///
+/// # Examples
+///
+/// ```
+/// let result = tea_timer::took! {
+///     1 + 1
+/// }; // This will print: "src/main.rs:3 took ..."
+/// assert_eq!(result, 2);
+///
+/// for i in 0..2 {
+///     tea_timer::took!(@"step {}", i;
+///         // ...any code
+///     ); // This will print: "step 0 took ..."
+/// }
+///
+/// let name = "parse";
+/// tea_timer::took!(@name = name;
+///     // ...any code
+/// ); // This will print: "parse took ..."
+/// ```
+#[macro_export]
+macro_rules! took {
+    ($($tt:tt)*) => {
+        $crate::__took!(@detect finish result; $($tt)*)
+    };
+}
+
+/// Like [`took!`], but logs the time it took using the `log` crate.
+///
+/// # Examples
+///
+/// ```
+/// let result = tea_timer::ltook!(@name = "sum", level = debug, target = "timing";
+///     1 + 1
+/// );
+/// assert_eq!(result, 2);
+/// ```
+#[macro_export]
+#[cfg(feature = "log")]
+macro_rules! ltook {
+    ($($tt:tt)*) => {
+        $crate::__took!(@detect finish_log result; $($tt)*)
+    };
+}
+
+/// Like [`took!`], but evaluates to a tuple of the block result and the measured duration.
+///
+/// # Examples
+///
+/// ```
+/// let (result, duration) = tea_timer::took_with_duration! {
+///     1 + 1
+/// };
+/// assert_eq!(result, 2);
+/// assert!(duration.as_secs() < 1);
+/// ```
+#[macro_export]
+macro_rules! took_with_duration {
+    ($($tt:tt)*) => {
+        $crate::__took!(@detect finish with_duration; $($tt)*)
+    };
+}
+
+/// Like [`ltook!`], but evaluates to a tuple of the block result and the measured duration.
+#[macro_export]
+#[cfg(feature = "log")]
+macro_rules! ltook_with_duration {
+    ($($tt:tt)*) => {
+        $crate::__took!(@detect finish_log with_duration; $($tt)*)
+    };
+}
+
+/// Shared implementation of the `took!` family of macros.
+///
+/// `$finish` is the method of [`ScopedTimer`] reporting the duration and `$output`
+/// is either `result` or `with_duration`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __took {
+    // after a leading `@`, a format string and its arguments up to the first `;` are the name
+    (@detect $finish:ident $output:ident; @ $fmt:literal $(, $arg:expr)* ; $($body:tt)*) => {
+        $crate::__took!(@expand $finish $output [name = ::std::format!($fmt $(, $arg)*)] $($body)*)
+    };
+    // otherwise options follow up to the first `;`
+    (@detect $finish:ident $output:ident; @ $($rest:tt)*) => {
+        $crate::__took!(@split $finish $output [] $($rest)*)
+    };
+    (@detect $finish:ident $output:ident; $($body:tt)*) => {
+        $crate::__took!(@expand $finish $output [] $($body)*)
+    };
+    (@split $finish:ident $output:ident [$($opts:tt)*] ; $($body:tt)*) => {
+        $crate::__took!(@expand $finish $output [$($opts)*] $($body)*)
+    };
+    (@split $finish:ident $output:ident [$($opts:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__took!(@split $finish $output [$($opts)* $next] $($rest)*)
+    };
+    (@expand $finish:ident result [$($opts:tt)*] $($body:tt)*) => {
+        {
+            #[allow(unused_mut)]
+            let mut timer = $crate::Timer::new(concat!(file!(), ":", line!()));
+            $crate::__timer_options!(timer; $($opts)*);
+            let timer = timer.into_scoped();
+            let res = {$($body)*};
+            timer.$finish();
+            res
+        }
+    };
+    (@expand $finish:ident with_duration [$($opts:tt)*] $($body:tt)*) => {
+        {
+            #[allow(unused_mut)]
+            let mut timer = $crate::Timer::new(concat!(file!(), ":", line!()));
+            $crate::__timer_options!(timer; $($opts)*);
+            let timer = timer.into_scoped();
+            let res = {$($body)*};
+            let duration = timer.$finish();
+            (res, duration)
+        }
+    };
+}
+
+/// Applies the options of the `took!` family of macros to a timer.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __timer_options {
+    ($timer:ident;) => {};
+    ($timer:ident; name = $name:literal $(, $($rest:tt)*)?) => {
+        $timer.task_name = ::std::format!($name);
+        $crate::__timer_options!($timer; $($($rest)*)?);
+    };
+    ($timer:ident; name = $name:expr $(, $($rest:tt)*)?) => {
+        $timer.task_name = ::std::string::ToString::to_string(&$name);
+        $crate::__timer_options!($timer; $($($rest)*)?);
+    };
+    ($timer:ident; level = $level:ident $(, $($rest:tt)*)?) => {
+        $timer.set_log_level($crate::__log_level!($level));
+        $crate::__timer_options!($timer; $($($rest)*)?);
+    };
+    ($timer:ident; level = $level:expr $(, $($rest:tt)*)?) => {
+        $timer.set_log_level($level);
+        $crate::__timer_options!($timer; $($($rest)*)?);
+    };
+    ($timer:ident; target = $target:expr $(, $($rest:tt)*)?) => {
+        $timer.set_log_target(&$target);
+        $crate::__timer_options!($timer; $($($rest)*)?);
+    };
+    ($timer:ident; span = $span:expr $(, $($rest:tt)*)?) => {
+        $timer.set_trace_span($span);
+        $crate::__timer_options!($timer; $($($rest)*)?);
+    };
+    ($timer:ident; threshold = $threshold:literal $(, $($rest:tt)*)?) => {
+        $timer.set_threshold(
+            $crate::parse_duration($threshold).expect("invalid threshold in took! options"),
+        );
+        $crate::__timer_options!($timer; $($($rest)*)?);
+    };
+    ($timer:ident; threshold = $threshold:expr $(, $($rest:tt)*)?) => {
+        $timer.set_threshold($threshold);
+        $crate::__timer_options!($timer; $($($rest)*)?);
+    };
+    ($timer:ident; throughput = $throughput:expr $(, $($rest:tt)*)?) => {
+        $timer.set_throughput($throughput);
+        $crate::__timer_options!($timer; $($($rest)*)?);
+    };
+    ($timer:ident; allocations = $allocations:expr $(, $($rest:tt)*)?) => {
+        $timer.set_alloc_tracking($allocations);
+        $crate::__timer_options!($timer; $($($rest)*)?);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "log")]
+macro_rules! __log_level {
+    (error) => {
+        $crate::__private::log::Level::Error
+    };
+    (warn) => {
+        $crate::__private::log::Level::Warn
+    };
+    (info) => {
+        $crate::__private::log::Level::Info
+    };
+    (debug) => {
+        $crate::__private::log::Level::Debug
+    };
+    (trace) => {
+        $crate::__private::log::Level::Trace
+    };
+    ($level:ident) => {
+        $level
+    };
+}
+
+/// Re-exports used by the macros of this crate.
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "log")]
+    pub use log;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timer_new() {
+        let timer = Timer::new("Test Task");
+        assert_eq!(timer.task_name, "Test Task");
     }
 
     #[test]
@@ -330,6 +1415,442 @@ mod tests {
         assert!(timer.duration_str().contains("ms"));
     }
 
+    #[test]
+    fn test_timer_pause_resume() {
+        let clock = ManualClock::new();
+        let mut timer = Timer::with_clock("Pause Test", clock.clone());
+        clock.advance(Duration::from_millis(10));
+        timer.pause();
+        timer.pause();
+        assert!(timer.is_paused());
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(timer.duration(), Duration::from_millis(10));
+        assert_eq!(timer.wall_duration(), Duration::from_millis(30));
+        timer.resume();
+        timer.resume();
+        assert!(!timer.is_paused());
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(timer.duration(), Duration::from_millis(15));
+        assert_eq!(timer.wall_duration(), Duration::from_millis(35));
+        assert_eq!(timer.took_str(), "Pause Test took 15.00ms");
+        assert_eq!(timer.elapsed_str(), "Pause Test elapsed 15.00ms");
+    }
+
+    #[test]
+    fn test_timer_laps() {
+        let clock = ManualClock::new();
+        let mut timer = Timer::with_clock("Lap Test", clock.clone());
+        assert_eq!(timer.laps_str(), "");
+        clock.advance(Duration::from_millis(5));
+        let first = timer.lap("first");
+        clock.advance(Duration::from_millis(7));
+        let second = timer.lap("second");
+        assert_eq!(first.split, Duration::from_millis(5));
+        assert_eq!(second.split, Duration::from_millis(7));
+        assert_eq!(second.total, Duration::from_millis(12));
+        assert_eq!(timer.laps(), &[first, second]);
+        assert_eq!(
+            timer.laps_str(),
+            "  lap      split    total\n  first   5.00ms   5.00ms\n  second  7.00ms  12.00ms"
+        );
+        timer.restart("Lap Test");
+        assert!(timer.laps().is_empty());
+        // columns are aligned by characters, not bytes
+        timer.lap("ünïcödé");
+        timer.lap("ascii");
+        assert_eq!(
+            timer.laps_str(),
+            "  lap      split  total\n  ünïcödé    0ns    0ns\n  ascii      0ns    0ns"
+        );
+    }
+
+    #[test]
+    fn test_took_with_clock() {
+        let clock = ManualClock::new();
+        let result = took_with_clock(
+            || {
+                clock.advance(Duration::from_millis(1));
+                42
+            },
+            "Clock Task",
+            clock.clone(),
+        );
+        assert_eq!(result, 42);
+    }
+
+    #[test]
+    #[cfg(all(feature = "cpu-time", target_os = "linux"))]
+    fn test_cpu_time() {
+        let mut timer = Timer::new("Spin");
+        assert!(timer.cpu_time().is_none());
+        timer.set_cpu_time(true);
+        let start = std::time::Instant::now();
+        while start.elapsed() < Duration::from_millis(20) {
+            std::hint::black_box((0..1_000u64).sum::<u64>());
+        }
+        let cpu = timer.cpu_time().unwrap();
+        assert!(cpu.total() >= Duration::from_millis(5), "{cpu:?}");
+        let took = timer.took_str();
+        assert!(took.starts_with("Spin took wall "), "{took}");
+        assert!(took.contains(", cpu ") && took.ends_with("x)"), "{took}");
+        timer.restart("Spin");
+        assert!(timer.cpu_time().unwrap().total() < cpu.total());
+        timer.set_cpu_time(false);
+        assert!(!timer.took_str().contains("cpu"));
+    }
+
+    #[test]
+    fn test_throughput() {
+        let buffer = sink::BufferSink::new();
+        let clock = ManualClock::new();
+        let mut timer = Timer::with_clock("Rows", clock.clone());
+        timer.set_sink(buffer.clone());
+        timer.set_throughput(Throughput::Elements(3_000));
+        assert_eq!(timer.took_str(), "Rows took 0ns");
+        clock.advance(Duration::from_millis(2));
+        assert_eq!(timer.elapsed_str(), "Rows elapsed 2.00ms (1.50 Melem/s)");
+        timer.set_threshold(Duration::from_millis(1));
+        timer.stop();
+        assert_eq!(
+            buffer.messages(),
+            ["Rows took 2.00ms (1.50 Melem/s) (over budget of 1.00ms by 1.00ms)"]
+        );
+        let len = took!(@throughput = Throughput::BytesDecimal(4); "data".len());
+        assert_eq!(len, 4);
+    }
+
+    #[cfg(feature = "log")]
+    mod log_capture {
+        use std::sync::{Mutex, Once};
+
+        pub static RECORDS: Mutex<Vec<(log::Level, String, String)>> = Mutex::new(Vec::new());
+
+        struct CaptureLogger;
+
+        impl log::Log for CaptureLogger {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record) {
+                RECORDS.lock().unwrap().push((
+                    record.level(),
+                    record.target().to_string(),
+                    record.args().to_string(),
+                ));
+            }
+
+            fn flush(&self) {}
+        }
+
+        /// Installs the capturing logger and returns the records logged to `target`.
+        pub fn records(target: &str) -> Vec<(log::Level, String)> {
+            static INIT: Once = Once::new();
+            INIT.call_once(|| {
+                log::set_logger(&CaptureLogger).unwrap();
+                log::set_max_level(log::LevelFilter::Trace);
+            });
+            RECORDS
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|record| record.1 == target)
+                .map(|record| (record.0, record.2.clone()))
+                .collect()
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "log")]
+    fn test_timer_log_at() {
+        log_capture::records("");
+        let clock = ManualClock::new();
+        let mut timer = Timer::with_clock("Log Test", clock.clone());
+        timer.set_log_target("test_timer_log_at");
+        clock.advance(Duration::from_millis(3));
+        timer.log();
+        timer.log_at(log::Level::Debug);
+        timer.set_log_level(log::Level::Trace);
+        timer.into_scoped().finish_log();
+        assert_eq!(
+            log_capture::records("test_timer_log_at"),
+            [
+                (log::Level::Info, "Log Test elapsed 3.00ms".to_string()),
+                (log::Level::Debug, "Log Test elapsed 3.00ms".to_string()),
+                (log::Level::Trace, "Log Test took 3.00ms".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "log")]
+    fn test_ltook_macro_options() {
+        log_capture::records("");
+        let result = ltook!(@level = debug, target = "test_ltook_macro_options";
+            let x = 20;
+            x + 22
+        );
+        assert_eq!(result, 42);
+        let (_, duration) = ltook_with_duration!(@target = "test_ltook_macro_options", level = log::Level::Warn; ());
+        let records = log_capture::records("test_ltook_macro_options");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].0, log::Level::Debug);
+        assert_eq!(records[1].0, log::Level::Warn);
+        assert!(duration.as_secs() < 1);
+        // a bare block is still accepted
+        let level = ltook! {
+            let level = 1;
+            level + 1
+        };
+        assert_eq!(level, 2);
+    }
+
+    #[test]
+    #[cfg(feature = "log")]
+    fn test_ltook_threshold() {
+        let target = "test_ltook_threshold";
+        log_capture::records("");
+        ltook!(@target = target, level = debug, threshold = "1h"; ());
+        ltook!(@target = target, level = debug, threshold = Duration::ZERO; ());
+        let mut timer = Timer::new("error");
+        timer.set_log_target(target);
+        timer.set_log_level(log::Level::Error);
+        timer.set_threshold(Duration::ZERO);
+        timer.into_scoped().finish_log();
+        let records = log_capture::records(target);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].0, log::Level::Warn);
+        assert!(records[0].1.contains(" (over budget of 0ns by "));
+        assert_eq!(records[1].0, log::Level::Error);
+    }
+
+    #[test]
+    #[cfg(feature = "log")]
+    fn test_log_threshold() {
+        let target = "test_log_threshold";
+        log_capture::records("");
+        let clock = ManualClock::new();
+        let mut timer = Timer::with_clock("Budget", clock.clone());
+        timer.set_log_target(target);
+        timer.set_threshold(Duration::from_millis(5));
+        timer.log();
+        clock.advance(Duration::from_millis(5));
+        timer.log_at(log::Level::Debug);
+        clock.advance(Duration::from_millis(1));
+        timer.log_at(log::Level::Debug);
+        assert_eq!(
+            log_capture::records(target),
+            [
+                (log::Level::Debug, "Budget elapsed 5.00ms".to_string()),
+                (
+                    log::Level::Warn,
+                    "Budget elapsed 6.00ms (over budget of 5.00ms by 1.00ms)".to_string()
+                )
+            ]
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    mod trace_capture {
+        use std::fmt::Debug;
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::Mutex;
+        use tracing::field::{Field, Visit};
+        use tracing::{span, Event, Metadata, Subscriber};
+
+        /// Fields of a span or an event, formatted with `Debug`.
+        #[derive(Default, Debug)]
+        pub struct Fields(pub Vec<(String, String)>);
+
+        impl Fields {
+            pub fn get(&self, name: &str) -> Option<&str> {
+                self.0.iter().find(|f| f.0 == name).map(|f| f.1.as_str())
+            }
+        }
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0
+                    .push((field.name().to_string(), format!("{value:?}")));
+            }
+        }
+
+        /// A subscriber recording spans and events together with the entered span.
+        #[derive(Default)]
+        pub struct Capture {
+            next_id: AtomicU64,
+            stack: Mutex<Vec<u64>>,
+            pub spans: Mutex<Vec<(u64, Fields)>>,
+            pub events: Mutex<Vec<(Option<u64>, Fields)>>,
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+                let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+                let mut fields = Fields::default();
+                attrs.record(&mut fields);
+                self.spans.lock().unwrap().push((id, fields));
+                span::Id::from_u64(id)
+            }
+
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                let parent = self.stack.lock().unwrap().last().copied();
+                self.events.lock().unwrap().push((parent, fields));
+            }
+
+            fn enter(&self, id: &span::Id) {
+                self.stack.lock().unwrap().push(id.into_u64());
+            }
+
+            fn exit(&self, _: &span::Id) {
+                self.stack.lock().unwrap().pop();
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn test_trace_events() {
+        use std::sync::Arc;
+
+        let capture = Arc::new(trace_capture::Capture::default());
+        tracing::subscriber::with_default(capture.clone(), || {
+            let result = took!(@span = true;
+                let clock = ManualClock::new();
+                let timer = Timer::with_clock("inner", clock.clone());
+                clock.advance(Duration::from_millis(2));
+                timer.trace_at(tracing::Level::DEBUG);
+                42
+            );
+            assert_eq!(result, 42);
+            ttook(|| (), "traced");
+        });
+        let spans = capture.spans.lock().unwrap();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[1].1.get("task"), Some("traced"));
+        let events = capture.events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        let (parent, fields) = &events[0];
+        assert_eq!(*parent, Some(spans[0].0));
+        assert_eq!(fields.get("task"), Some("inner"));
+        assert_eq!(fields.get("elapsed_ns"), Some("2000000"));
+        assert_eq!(fields.get("status"), Some("\"running\""));
+        let (parent, fields) = &events[1];
+        assert_eq!(*parent, Some(spans[1].0));
+        assert_eq!(fields.get("task"), Some("traced"));
+        assert_eq!(fields.get("status"), Some("\"finished\""));
+    }
+
+    #[test]
+    #[cfg(feature = "macros")]
+    fn test_timed_attribute() {
+        use std::future::Future;
+
+        #[timed(name = "timed parse")]
+        fn parse(input: &str) -> Result<i32, std::num::ParseIntError> {
+            let value = input.parse::<i32>()?;
+            Ok(value * 2)
+        }
+
+        #[timed(level = "debug")]
+        fn logged() -> u8 {
+            1
+        }
+
+        #[timed(threshold = "1 h")]
+        async fn load(value: usize) -> usize {
+            value + 1
+        }
+
+        tree::set_recording(true);
+        tree::reset();
+        assert_eq!(parse("21"), Ok(42));
+        assert!(parse("x").is_err());
+        let mut future = std::pin::pin!(load(41));
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        assert_eq!(future.as_mut().poll(&mut cx), std::task::Poll::Ready(42));
+        let roots = tree::take();
+        assert_eq!(roots[0].name, "timed parse");
+        assert_eq!(roots[0].calls, 2);
+        // async functions are not part of the tree
+        assert_eq!(roots.len(), 1);
+        assert_eq!(logged(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "macros")]
+    fn test_timed_method_names() {
+        struct Parser;
+        struct Loader;
+
+        impl Parser {
+            #[timed]
+            fn new() -> Self {
+                Parser
+            }
+        }
+
+        impl Loader {
+            #[timed]
+            fn new() -> Self {
+                Loader
+            }
+        }
+
+        tree::set_recording(true);
+        tree::reset();
+        Parser::new();
+        Loader::new();
+        Loader::new();
+        let roots = tree::take();
+        let prefix = "tea_timer::tests::test_timed_method_names";
+        assert_eq!(roots[0].name, format!("{prefix}::Parser::new"));
+        assert_eq!(roots[0].calls, 1);
+        assert_eq!(roots[1].name, format!("{prefix}::Loader::new"));
+        assert_eq!(roots[1].calls, 2);
+    }
+
+    #[test]
+    fn test_took_macro_names() {
+        tree::set_recording(true);
+        tree::reset();
+        let i = 3;
+        let name = String::from("expr name");
+        assert_eq!(took!(@"step {}", i; i + 1), 4);
+        assert_eq!(took!(@"step {i}"; i), 3);
+        took!(@name = "inline {i}"; ());
+        took!(@name = name; ());
+        took!(());
+        let (_, duration) = took_with_duration!(@name = format!("with {}", "duration"); ());
+        assert!(duration.as_secs() < 1);
+        // blocks looking like a name or options are still valid blocks
+        assert_eq!(took!("literal"), "literal");
+        #[allow(clippy::no_effect)]
+        let last = took! { 1; 2 };
+        assert_eq!(last, 2);
+        let threshold;
+        let next = took! { threshold = 5; threshold + 1 };
+        assert_eq!(next, 6);
+        let names: Vec<_> = tree::take().into_iter().map(|node| node.name).collect();
+        assert_eq!(names.len(), 8);
+        assert_eq!(names[..3], ["step 3", "inline 3", "expr name"]);
+        assert!(names[3].starts_with("src/lib.rs:"));
+        assert_eq!(names[4], "with duration");
+        assert!(names[5..]
+            .iter()
+            .all(|name| name.starts_with("src/lib.rs:")));
+    }
+
     #[test]
     fn test_timer_default() {
         let timer = Timer::default();
@@ -356,7 +1877,56 @@ mod tests {
         };
         assert_eq!(result, 42);
     }
-    // Note: We can't easily test the `stop` method as it prints to stdout.
-    // In a real-world scenario, we might want to refactor to return the duration
-    // instead of printing it, which would make it more testable.
+
+    #[test]
+    fn test_timer_stop() {
+        let clock = ManualClock::new();
+        let mut timer = Timer::with_clock("Stop Test", clock.clone());
+        clock.advance(Duration::from_millis(8));
+        timer.pause();
+        clock.advance(Duration::from_millis(8));
+        assert_eq!(timer.stop(), Duration::from_millis(8));
+    }
+
+    #[test]
+    fn test_timer_sink() {
+        let buffer = sink::BufferSink::new();
+        let clock = ManualClock::new();
+        let mut timer = Timer::with_clock("Sink Test", clock.clone());
+        timer.set_sink(buffer.clone());
+        clock.advance(Duration::from_millis(2));
+        timer.elapsed();
+        timer.lap("load");
+        timer.stop();
+        assert_eq!(
+            buffer.messages(),
+            [
+                "Sink Test elapsed 2.00ms",
+                "Sink Test took 2.00ms\n  lap    split   total\n  load  2.00ms  2.00ms",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_took_with_duration() {
+        let (result, duration) = took_with_duration(
+            || {
+                sleep(Duration::from_millis(10));
+                42
+            },
+            "Test Task",
+        );
+        assert_eq!(result, 42);
+        assert!(duration.as_millis() >= 10);
+    }
+
+    #[test]
+    fn test_took_with_duration_macro() {
+        let (result, duration) = took_with_duration! {
+            sleep(Duration::from_millis(10));
+            42
+        };
+        assert_eq!(result, 42);
+        assert!(duration.as_millis() >= 10);
+    }
 }
@@ -0,0 +1,294 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::DurationUnit;
+
+/// Maximum number of fraction digits taken into account, more digits are below nanosecond precision.
+const MAX_FRACTION_DIGITS: usize = 24;
+
+/// An error returned by [`parse_duration`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseDurationError {
+    /// The input contains no duration.
+    Empty,
+    /// A number was expected at the given byte offset.
+    InvalidNumber { position: usize },
+    /// The number ending at the given byte offset is not followed by a unit.
+    MissingUnit { position: usize },
+    /// The unit at the given byte offset is not known.
+    UnknownUnit { unit: String, position: usize },
+    /// The unit at the given byte offset is not smaller than the unit before it.
+    UnitOrder { unit: String, position: usize },
+    /// The duration does not fit into a [`Duration`].
+    Overflow,
+}
+
+impl fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseDurationError::Empty => write!(f, "empty duration"),
+            ParseDurationError::InvalidNumber { position } => {
+                write!(f, "expected a number at position {position}")
+            }
+            ParseDurationError::MissingUnit { position } => {
+                write!(f, "missing unit after the number at position {position}")
+            }
+            ParseDurationError::UnknownUnit { unit, position } => {
+                write!(
+                    f,
+                    "unknown unit `{unit}` at position {position}, expected one of ns, µs, us, ms, s, m, h, d"
+                )
+            }
+            ParseDurationError::UnitOrder { unit, position } => write!(
+                f,
+                "unit `{unit}` at position {position} must be smaller than the unit before it"
+            ),
+            ParseDurationError::Overflow => write!(f, "duration is too large"),
+        }
+    }
+}
+
+impl std::error::Error for ParseDurationError {}
+
+fn parse_unit(unit: &str) -> Option<DurationUnit> {
+    match unit {
+        "ns" => Some(DurationUnit::Nanos),
+        // both the micro sign and the greek letter mu are accepted
+        "µs" | "μs" | "us" => Some(DurationUnit::Micros),
+        "ms" => Some(DurationUnit::Millis),
+        "s" => Some(DurationUnit::Secs),
+        "m" => Some(DurationUnit::Mins),
+        "h" => Some(DurationUnit::Hours),
+        "d" => Some(DurationUnit::Days),
+        _ => None,
+    }
+}
+
+/// Parses a human readable duration.
+///
+/// Every string produced by [`format_duration`](crate::display::format_duration) and
+/// [`DurationFormat`](crate::DurationFormat) is accepted, e.g. `800ns`, `12.34ms`,
+/// `5.00µs`, `5.00us` or `5.00s`, as well as compound durations such as `1h2m3.5s`
+/// or `1m 23.4s` whose units are in descending order.
+///
+/// Formatting rounds to the printed precision, so durations within half a step of
+/// [`Duration::MAX`] are printed as a larger value, e.g. `18446744073709551616.00s`,
+/// and parsing them back returns [`ParseDurationError::Overflow`].
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::parse_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_duration("12.34ms"), Ok(Duration::from_micros(12_340)));
+/// assert_eq!(parse_duration("1h2m3.5s"), Ok(Duration::from_millis(3_723_500)));
+/// assert!(parse_duration("12 parsecs").is_err());
+/// ```
+pub fn parse_duration(input: &str) -> Result<Duration, ParseDurationError> {
+    let bytes = input.as_bytes();
+    let mut pos = 0;
+    let mut total: u128 = 0;
+    let mut last_unit: Option<DurationUnit> = None;
+    loop {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos == bytes.len() {
+            break;
+        }
+        // number: digits with an optional fraction
+        let int_start = pos;
+        while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        let int_digits = &input[int_start..pos];
+        let mut frac_digits = "";
+        if pos < bytes.len() && bytes[pos] == b'.' {
+            pos += 1;
+            let frac_start = pos;
+            while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                pos += 1;
+            }
+            frac_digits = &input[frac_start..pos];
+        }
+        if int_digits.is_empty() && frac_digits.is_empty() {
+            return Err(ParseDurationError::InvalidNumber {
+                position: int_start,
+            });
+        }
+        // unit: everything up to the next digit or whitespace
+        let number_end = pos;
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        let unit_start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_digit() && !bytes[pos].is_ascii_whitespace()
+        {
+            pos += 1;
+        }
+        let unit_str = &input[unit_start..pos];
+        if unit_str.is_empty() {
+            return Err(ParseDurationError::MissingUnit {
+                position: number_end,
+            });
+        }
+        let unit = parse_unit(unit_str).ok_or_else(|| ParseDurationError::UnknownUnit {
+            unit: unit_str.to_string(),
+            position: unit_start,
+        })?;
+        if last_unit.is_some_and(|last| unit >= last) {
+            return Err(ParseDurationError::UnitOrder {
+                unit: unit_str.to_string(),
+                position: unit_start,
+            });
+        }
+        last_unit = Some(unit);
+        total = total
+            .checked_add(component_nanos(int_digits, frac_digits, unit)?)
+            .ok_or(ParseDurationError::Overflow)?;
+    }
+    if last_unit.is_none() {
+        return Err(ParseDurationError::Empty);
+    }
+    let secs = u64::try_from(total / DurationUnit::Secs.nanos())
+        .map_err(|_| ParseDurationError::Overflow)?;
+    Ok(Duration::new(
+        secs,
+        (total % DurationUnit::Secs.nanos()) as u32,
+    ))
+}
+
+/// Converts `int.frac` of `unit` into nanoseconds, rounding to the nearest nanosecond.
+fn component_nanos(
+    int_digits: &str,
+    frac_digits: &str,
+    unit: DurationUnit,
+) -> Result<u128, ParseDurationError> {
+    let unit_nanos = unit.nanos();
+    let int = if int_digits.is_empty() {
+        0
+    } else {
+        int_digits
+            .parse::<u128>()
+            .map_err(|_| ParseDurationError::Overflow)?
+    };
+    let mut nanos = int
+        .checked_mul(unit_nanos)
+        .ok_or(ParseDurationError::Overflow)?;
+    let frac_digits = &frac_digits[..frac_digits.len().min(MAX_FRACTION_DIGITS)];
+    if !frac_digits.is_empty() {
+        let frac: u128 = frac_digits.parse().unwrap();
+        let scale = 10u128.pow(frac_digits.len() as u32);
+        nanos += (2 * frac * unit_nanos + scale) / (2 * scale);
+    }
+    Ok(nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::format_duration;
+    use crate::DurationFormat;
+
+    #[test]
+    fn test_parse_duration() {
+        for (input, expected) in [
+            ("800ns", Duration::from_nanos(800)),
+            ("12.34ms", Duration::from_micros(12_340)),
+            ("5.00s", Duration::from_secs(5)),
+            ("1.50µs", Duration::from_nanos(1_500)),
+            ("1.50μs", Duration::from_nanos(1_500)),
+            ("1.50us", Duration::from_nanos(1_500)),
+            (".5s", Duration::from_millis(500)),
+            ("2.h", Duration::from_secs(7_200)),
+            ("1h2m3.5s", Duration::from_millis(3_723_500)),
+            (" 1d 1h ", Duration::from_secs(90_000)),
+            ("1m 23.4s", Duration::from_millis(83_400)),
+            ("3 ms", Duration::from_millis(3)),
+            ("0.0000000005s", Duration::from_nanos(1)),
+            ("1.0000000004s", Duration::from_secs(1)),
+        ] {
+            assert_eq!(parse_duration(input), Ok(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_parse_duration_errors() {
+        use ParseDurationError::*;
+        assert_eq!(parse_duration(""), Err(Empty));
+        assert_eq!(parse_duration("  "), Err(Empty));
+        assert_eq!(parse_duration("ms"), Err(InvalidNumber { position: 0 }));
+        assert_eq!(parse_duration("-1s"), Err(InvalidNumber { position: 0 }));
+        assert_eq!(parse_duration("12"), Err(MissingUnit { position: 2 }));
+        assert_eq!(parse_duration("12 3s"), Err(MissingUnit { position: 2 }));
+        assert_eq!(parse_duration("1s 5"), Err(MissingUnit { position: 4 }));
+        assert_eq!(
+            parse_duration("3 weeks"),
+            Err(UnknownUnit {
+                unit: "weeks".to_string(),
+                position: 2
+            })
+        );
+        assert_eq!(
+            parse_duration("1s2h"),
+            Err(UnitOrder {
+                unit: "h".to_string(),
+                position: 3
+            })
+        );
+        assert_eq!(
+            parse_duration("1s1s").unwrap_err().to_string(),
+            "unit `s` at position 3 must be smaller than the unit before it"
+        );
+        assert_eq!(parse_duration("1000000000000000000000000d"), Err(Overflow));
+        // rounding pushes the largest durations beyond `Duration::MAX`
+        assert_eq!(
+            parse_duration(&format_duration(Duration::MAX)),
+            Err(Overflow)
+        );
+    }
+
+    /// Durations just below a unit, which round up into the next one, followed by a
+    /// small xorshift generator, so the property tests are reproducible.
+    fn durations() -> impl Iterator<Item = Duration> {
+        let boundaries = [999, 999_999, 999_995_000, 59_999_999_999].map(Duration::from_nanos);
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        boundaries.into_iter().chain((0..2_000).map(move |i| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            // spread the durations over all magnitudes from nanoseconds to weeks
+            let magnitude = 1u64 << (i % 50);
+            Duration::from_nanos(state % magnitude)
+        }))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let formats = [
+            DurationFormat::new(),
+            DurationFormat::new().precision(0),
+            DurationFormat::new().precision(5).ascii(true),
+            DurationFormat::new().max_unit(crate::DurationUnit::Days),
+            DurationFormat::new().compound(true),
+            DurationFormat::new().compound(true).precision(1),
+            DurationFormat::new().unit(crate::DurationUnit::Millis),
+        ];
+        for duration in durations() {
+            let formatted = format_duration(duration);
+            let parsed = parse_duration(&formatted).unwrap();
+            assert_eq!(format_duration(parsed), formatted);
+            for format in formats {
+                let formatted = format.format(duration);
+                let parsed = parse_duration(&formatted).unwrap();
+                assert_eq!(format.format(parsed), formatted, "{duration:?} {format:?}");
+            }
+            let exact = DurationFormat::new()
+                .unit(crate::DurationUnit::Secs)
+                .precision(9)
+                .format(duration);
+            assert_eq!(parse_duration(&exact), Ok(duration));
+        }
+    }
+}
@@ -0,0 +1,356 @@
+//! Structured timing records.
+//!
+//! A [`TimingRecord`] holds everything a timer measured, so it can be stored or
+//! shipped instead of a formatted string. It is returned by
+//! [`Timer::stop_record`](crate::Timer::stop_record),
+//! [`ScopedTimer::finish_record`](crate::ScopedTimer::finish_record) and
+//! [`took_record`](crate::took_record).
+//!
+//! With the `serde` feature, records implement `Serialize` and `Deserialize`, with durations
+//! as integer nanoseconds. Use `human` with `#[serde(with)]` to serialize them as human
+//! readable strings such as `"12.5ms"` instead, and the modules in `serde_duration` for
+//! durations of your own types.
+//!
+//! # Examples
+//!
+//! ```
+//! use tea_timer::Timer;
+//!
+//! let mut timer = Timer::new("Import");
+//! timer.set_metadata("file", "users.csv");
+//! timer.lap("parse");
+//! let record = timer.stop_record(); // This will print the report as `stop` does
+//! assert_eq!(record.name, "Import");
+//! assert_eq!(record.laps.len(), 1);
+//! assert_eq!(record.metadata["file"], "users.csv");
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use crate::Lap;
+
+/// Everything measured by a timer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimingRecord {
+    /// The task name of the timer.
+    pub name: String,
+    /// The wall-clock time the timer started or was last restarted.
+    pub start: SystemTime,
+    /// The active duration of the task.
+    #[cfg_attr(feature = "serde", serde(with = "serde_duration::nanos"))]
+    pub duration: Duration,
+    /// The name of the thread the record was taken on, or its id if it has no name.
+    pub thread: String,
+    pub laps: Vec<Lap>,
+    /// Metadata set with [`Timer::set_metadata`](crate::Timer::set_metadata).
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// Returns the name of the current thread, or its id if it has no name.
+pub(crate) fn current_thread() -> String {
+    let thread = std::thread::current();
+    match thread.name() {
+        Some(name) => name.to_string(),
+        None => format!("{:?}", thread.id()),
+    }
+}
+
+/// Serialization of durations, for `#[serde(with)]`.
+///
+/// This module is only available when the `serde` feature is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use std::time::Duration;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Config {
+///     #[serde(with = "tea_timer::record::serde_duration::human")]
+///     timeout: Duration,
+///     #[serde(with = "tea_timer::record::serde_duration::nanos")]
+///     interval: Duration,
+/// }
+/// ```
+#[cfg(feature = "serde")]
+pub mod serde_duration {
+    /// Durations as an integer number of nanoseconds.
+    pub mod nanos {
+        use std::fmt;
+        use std::time::Duration;
+
+        use serde::de::{self, Visitor};
+        use serde::{Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            duration: &Duration,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            let nanos = u64::try_from(duration.as_nanos())
+                .map_err(|_| serde::ser::Error::custom("duration overflows u64 nanoseconds"))?;
+            serializer.serialize_u64(nanos)
+        }
+
+        struct NanosVisitor;
+
+        impl Visitor<'_> for NanosVisitor {
+            type Value = Duration;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a number of nanoseconds")
+            }
+
+            fn visit_u64<E: de::Error>(self, nanos: u64) -> Result<Duration, E> {
+                Ok(Duration::from_nanos(nanos))
+            }
+
+            fn visit_i64<E: de::Error>(self, nanos: i64) -> Result<Duration, E> {
+                u64::try_from(nanos)
+                    .map(Duration::from_nanos)
+                    .map_err(|_| E::custom("negative duration"))
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Duration, D::Error> {
+            deserializer.deserialize_u64(NanosVisitor)
+        }
+    }
+
+    /// Durations as an exact human readable string like `"1.5s"`, which
+    /// [`parse_duration`](crate::parse_duration) reads back.
+    pub mod human {
+        use std::fmt;
+        use std::time::Duration;
+
+        use serde::de::{self, Visitor};
+        use serde::{Deserializer, Serializer};
+
+        use crate::parse_duration;
+
+        pub fn serialize<S: Serializer>(
+            duration: &Duration,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&format(*duration))
+        }
+
+        /// Formats the duration exactly in the largest fitting unit up to seconds,
+        /// without trailing zeros.
+        pub(crate) fn format(duration: Duration) -> String {
+            let nanos = duration.as_nanos();
+            let (scale, digits, unit) = match nanos {
+                1_000_000_000.. => (1_000_000_000, 9, "s"),
+                1_000_000.. => (1_000_000, 6, "ms"),
+                1_000.. => (1_000, 3, "µs"),
+                _ => return format!("{nanos}ns"),
+            };
+            let (int, frac) = (nanos / scale, nanos % scale);
+            if frac == 0 {
+                return format!("{int}{unit}");
+            }
+            let frac = format!("{frac:0digits$}");
+            format!("{int}.{}{unit}", frac.trim_end_matches('0'))
+        }
+
+        struct HumanVisitor;
+
+        impl Visitor<'_> for HumanVisitor {
+            type Value = Duration;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a duration string")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Duration, E> {
+                parse_duration(s).map_err(E::custom)
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Duration, D::Error> {
+            deserializer.deserialize_str(HumanVisitor)
+        }
+    }
+}
+
+/// Serialization of a [`TimingRecord`] with human readable durations, for `#[serde(with)]`.
+///
+/// This module is only available when the `serde` feature is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::{record, TimingRecord, Timer};
+///
+/// #[derive(serde::Serialize)]
+/// struct Report {
+///     #[serde(with = "record::human")]
+///     import: TimingRecord,
+/// }
+///
+/// let report = Report { import: Timer::new("Import").record() };
+/// let json = serde_json::to_string(&report).unwrap();
+///
+/// // a record can also be serialized on its own
+/// let mut json = Vec::new();
+/// record::human::serialize(&report.import, &mut serde_json::Serializer::new(&mut json)).unwrap();
+/// ```
+#[cfg(feature = "serde")]
+pub mod human {
+    use std::collections::BTreeMap;
+    use std::time::{Duration, SystemTime};
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{serde_duration, TimingRecord};
+    use crate::Lap;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "TimingRecord")]
+    struct HumanRecord {
+        name: String,
+        start: SystemTime,
+        #[serde(with = "serde_duration::human")]
+        duration: Duration,
+        thread: String,
+        #[serde(with = "laps")]
+        laps: Vec<Lap>,
+        metadata: BTreeMap<String, String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "Lap")]
+    struct HumanLap {
+        name: String,
+        #[serde(with = "serde_duration::human")]
+        split: Duration,
+        #[serde(with = "serde_duration::human")]
+        total: Duration,
+    }
+
+    /// Laps with human readable durations.
+    mod laps {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        use super::HumanLap;
+        use crate::Lap;
+
+        struct LapRef<'a>(&'a Lap);
+
+        impl Serialize for LapRef<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                HumanLap::serialize(self.0, serializer)
+            }
+        }
+
+        struct OwnedLap(Lap);
+
+        impl<'de> Deserialize<'de> for OwnedLap {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                HumanLap::deserialize(deserializer).map(OwnedLap)
+            }
+        }
+
+        pub fn serialize<S: Serializer>(laps: &[Lap], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(laps.iter().map(LapRef))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<Lap>, D::Error> {
+            let laps = Vec::<OwnedLap>::deserialize(deserializer)?;
+            Ok(laps.into_iter().map(|OwnedLap(lap)| lap).collect())
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        record: &TimingRecord,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        HumanRecord::serialize(record, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<TimingRecord, D::Error> {
+        HumanRecord::deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ManualClock, Timer};
+
+    #[test]
+    fn test_timer_record() {
+        let clock = ManualClock::new();
+        let before = SystemTime::now();
+        let mut timer = Timer::with_clock("Record", clock.clone());
+        timer.set_metadata("rows", 3);
+        clock.advance(Duration::from_millis(2));
+        timer.lap("read");
+        clock.advance(Duration::from_millis(3));
+        let record = timer.record();
+        assert_eq!(record.name, "Record");
+        assert_eq!(record.duration, Duration::from_millis(5));
+        assert!(record.start >= before && record.start <= SystemTime::now());
+        assert_eq!(record.laps[0].name, "read");
+        assert_eq!(record.metadata["rows"], "3");
+        assert_eq!(record.thread, current_thread());
+        assert_eq!(timer.stop_record(), record);
+    }
+
+    #[test]
+    fn test_took_record() {
+        let (result, record) = crate::took_record(|| 6 * 7, "answer");
+        assert_eq!(result, 42);
+        assert_eq!(record.name, "answer");
+        assert!(record.laps.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        use serde_duration::human::format;
+
+        assert_eq!(format(Duration::ZERO), "0ns");
+        assert_eq!(format(Duration::from_millis(12_500)), "12.5s");
+        assert_eq!(format(Duration::from_nanos(1_000_000_001)), "1.000000001s");
+        assert_eq!(format(Duration::from_secs(5400)), "5400s");
+        assert_eq!(format(Duration::from_nanos(1_020)), "1.02µs");
+        let large = Duration::new(100_000_000, 1);
+        assert_eq!(format(large), "100000000.000000001s");
+        assert_eq!(crate::parse_duration(&format(large)), Ok(large));
+
+        let record = TimingRecord {
+            name: "Serde".to_string(),
+            start: SystemTime::UNIX_EPOCH + Duration::from_secs(1),
+            duration: Duration::from_micros(1_500),
+            thread: "main".to_string(),
+            laps: vec![Lap {
+                name: "lap".to_string(),
+                split: Duration::from_micros(500),
+                total: Duration::from_micros(500),
+            }],
+            metadata: BTreeMap::from([("k".to_string(), "v".to_string())]),
+        };
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(json.contains(r#""duration":1500000"#), "{json}");
+        assert_eq!(serde_json::from_str::<TimingRecord>(&json).unwrap(), record);
+
+        let mut human = Vec::new();
+        human::serialize(&record, &mut serde_json::Serializer::new(&mut human)).unwrap();
+        let human = String::from_utf8(human).unwrap();
+        assert!(human.contains(r#""duration":"1.5ms""#), "{human}");
+        assert!(human.contains(r#""split":"500µs""#), "{human}");
+        let parsed = human::deserialize(&mut serde_json::Deserializer::from_str(&human)).unwrap();
+        assert_eq!(parsed, record);
+    }
+}
@@ -0,0 +1,265 @@
+//! A process-wide registry aggregating repeated measurements by task name.
+//!
+//! Measurements can be recorded explicitly with [`record`]. When recording is
+//! enabled with [`set_recording`], [`Timer::stop`](crate::Timer::stop),
+//! [`took`](fn@crate::took), `ltook` and the macros record into the
+//! registry silently instead of printing or logging every measurement.
+//!
+//! # Examples
+//!
+//! ```
+//! use tea_timer::{registry, took};
+//!
+//! registry::set_recording(true);
+//! for i in 0..1000 {
+//!     took(|| i * 2, "parse");
+//! }
+//! registry::set_recording(false);
+//! assert_eq!(registry::get("parse").unwrap().count(), 1000);
+//! registry::report(); // prints a summary table sorted by total time, with percentiles
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::{display, lock_ignoring_poison, sink, Histogram};
+
+/// Aggregated statistics of the measurements recorded for one task.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    count: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+    /// Running mean in nanoseconds.
+    mean: f64,
+    /// Running sum of squared deviations from the mean, in nanoseconds squared.
+    m2: f64,
+    histogram: Histogram,
+}
+
+impl Default for Stats {
+    #[inline]
+    fn default() -> Self {
+        Stats {
+            count: 0,
+            total: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+            mean: 0.,
+            m2: 0.,
+            histogram: Histogram::new(),
+        }
+    }
+}
+
+impl Stats {
+    /// Adds a measurement to the statistics.
+    pub fn record(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.min = self.min.min(duration);
+        self.max = self.max.max(duration);
+        // Welford's online algorithm
+        let nanos = duration.as_nanos() as f64;
+        let delta = nanos - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (nanos - self.mean);
+        self.histogram.record(duration);
+    }
+
+    /// Returns the number of measurements.
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the sum of all measurements.
+    #[inline]
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Returns the shortest measurement, or zero if nothing was recorded.
+    #[inline]
+    pub fn min(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.min
+        }
+    }
+
+    /// Returns the longest measurement.
+    #[inline]
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Returns the mean of the measurements.
+    #[inline]
+    pub fn mean(&self) -> Duration {
+        Duration::from_nanos(self.mean.round() as u64)
+    }
+
+    /// Returns the sample standard deviation of the measurements.
+    #[inline]
+    pub fn std_dev(&self) -> Duration {
+        if self.count < 2 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos((self.m2 / (self.count - 1) as f64).sqrt().round() as u64)
+        }
+    }
+
+    /// Returns the duration below which `percentile` percent of the measurements fall.
+    #[inline]
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        self.histogram.percentile(percentile)
+    }
+
+    /// Returns the histogram of the measurements.
+    #[inline]
+    pub fn histogram(&self) -> &Histogram {
+        &self.histogram
+    }
+}
+
+static REGISTRY: Mutex<BTreeMap<String, Stats>> = Mutex::new(BTreeMap::new());
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+#[inline]
+fn registry() -> MutexGuard<'static, BTreeMap<String, Stats>> {
+    lock_ignoring_poison(&REGISTRY)
+}
+
+/// Enables or disables silent recording of finished timers into the registry.
+#[inline]
+pub fn set_recording(enabled: bool) {
+    RECORDING.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns `true` if finished timers are recorded into the registry instead of being reported.
+#[inline]
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// Records a measurement for the given task name.
+pub fn record(task_name: &str, duration: Duration) {
+    let mut registry = registry();
+    match registry.get_mut(task_name) {
+        Some(stats) => stats.record(duration),
+        None => {
+            let mut stats = Stats::default();
+            stats.record(duration);
+            registry.insert(task_name.to_string(), stats);
+        }
+    }
+}
+
+/// Returns the statistics recorded for the given task name.
+#[inline]
+pub fn get(task_name: &str) -> Option<Stats> {
+    registry().get(task_name).cloned()
+}
+
+/// Returns the statistics of all tasks, sorted by total time in descending order.
+pub fn snapshot() -> Vec<(String, Stats)> {
+    let mut stats: Vec<_> = registry()
+        .iter()
+        .map(|(name, stats)| (name.clone(), stats.clone()))
+        .collect();
+    stats.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(&b.0)));
+    stats
+}
+
+/// Removes all recorded statistics.
+#[inline]
+pub fn reset() {
+    registry().clear();
+}
+
+/// Returns a summary table of all tasks, sorted by total time in descending order.
+pub fn report_str() -> String {
+    format_stats(&snapshot())
+}
+
+/// Prints a summary table of all tasks to the global [`sink`], sorted by total time in descending order.
+#[inline]
+pub fn report() {
+    sink::emit(None, &report_str());
+}
+
+fn format_stats(stats: &[(String, Stats)]) -> String {
+    let mut rows = vec![[
+        "name", "count", "total", "min", "max", "mean", "std", "p50", "p99",
+    ]
+    .map(String::from)];
+    for (name, stats) in stats {
+        rows.push([
+            name.clone(),
+            stats.count.to_string(),
+            display::format_duration(stats.total),
+            display::format_duration(stats.min()),
+            display::format_duration(stats.max),
+            display::format_duration(stats.mean()),
+            display::format_duration(stats.std_dev()),
+            display::format_duration(stats.histogram.p50()),
+            display::format_duration(stats.histogram.p99()),
+        ]);
+    }
+    display::format_table(&rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let mut stats = Stats::default();
+        assert_eq!(stats.min(), Duration::ZERO);
+        for ms in [2, 4, 4, 4, 5, 5, 7, 9] {
+            stats.record(Duration::from_millis(ms));
+        }
+        assert_eq!(stats.count(), 8);
+        assert_eq!(stats.total(), Duration::from_millis(40));
+        assert_eq!(stats.min(), Duration::from_millis(2));
+        assert_eq!(stats.max(), Duration::from_millis(9));
+        assert_eq!(stats.mean(), Duration::from_millis(5));
+        // sample variance is 32 / 7 ms^2
+        assert_eq!(stats.std_dev().as_micros(), 2138);
+        assert_eq!(stats.histogram().count(), 8);
+        assert_eq!(stats.percentile(100.), stats.max());
+    }
+
+    #[test]
+    fn test_format_stats() {
+        let mut fast = Stats::default();
+        fast.record(Duration::from_millis(2));
+        fast.record(Duration::from_millis(4));
+        let table = format_stats(&[("fast".to_string(), fast)]);
+        assert_eq!(
+            table,
+            [
+                "name  count   total     min     max    mean     std     p50     p99",
+                "fast      2  6.00ms  2.00ms  4.00ms  3.00ms  1.41ms  2.00ms  4.00ms",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_registry_record() {
+        record("registry::test_registry_record", Duration::from_millis(1));
+        record("registry::test_registry_record", Duration::from_millis(2));
+        let stats = get("registry::test_registry_record").unwrap();
+        assert_eq!(stats.count(), 2);
+        assert!(snapshot()
+            .iter()
+            .any(|(name, _)| name == "registry::test_registry_record"));
+    }
+}
@@ -0,0 +1,484 @@
+use std::ops::{Deref, DerefMut};
+#[cfg(feature = "tracing")]
+use std::thread::ThreadId;
+use std::time::Duration;
+
+use crate::{chrome, registry, tree, Clock, InstantClock, Timer, TimingRecord};
+
+/// A guard which reports the duration of the enclosing scope when it is dropped.
+///
+/// The report is emitted on every way out of the scope, including early returns,
+/// `?` propagation and panics. When the scope is left by unwinding, the report is
+/// marked with `(panicked)`.
+///
+/// The guard dereferences to the underlying [`Timer`], so laps can be recorded and
+/// the timer can be paused while it is alive.
+///
+/// Scoped timers created while another one is alive on the same thread become its
+/// children in the [`tree`](crate::tree) of nested timings, and their begin and end
+/// are recorded for [`chrome`](crate::chrome) tracing, when these are recording.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::Timer;
+///
+/// fn parse(input: &str) -> Result<i32, std::num::ParseIntError> {
+///     let _guard = Timer::scoped("parse");
+///     let value = input.parse::<i32>()?;
+///     Ok(value * 2)
+/// } // "parse took ..." is printed here, even if `?` returned early
+///
+/// assert_eq!(parse("21"), Ok(42));
+/// assert!(parse("x").is_err());
+/// ```
+pub struct ScopedTimer<C: Clock = InstantClock> {
+    timer: Timer<C>,
+    /// The scope in the timing tree, if it was recording when the scope was entered.
+    entered: Option<tree::Entered>,
+    finished: bool,
+    report: Report,
+    /// The thread id of the recorded Chrome trace begin event, if any.
+    chrome_thread: Option<u64>,
+    /// The span, if the timer was set to open one, and the thread it was entered on.
+    #[cfg(feature = "tracing")]
+    span: Option<(tracing::Span, Option<ThreadId>)>,
+}
+
+/// How a [`ScopedTimer`] reports its duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Report {
+    /// Write to the sink of the timer.
+    Sink,
+    #[cfg(feature = "log")]
+    Log,
+    #[cfg(feature = "tracing")]
+    Trace,
+}
+
+impl<C: Clock> ScopedTimer<C> {
+    /// Wraps an existing timer in a scope guard.
+    ///
+    /// The span of the timer, see `Timer::set_trace_span`, is entered on the current
+    /// thread. It is only exited when the guard is dropped on the same thread.
+    #[inline]
+    pub fn new(timer: Timer<C>) -> Self {
+        let entered = tree::enter(&timer.task_name);
+        Self::with_entered(timer, entered, true)
+    }
+
+    /// Wraps an existing timer in a scope guard which is not part of the [`tree`](crate::tree)
+    /// of nested timings.
+    ///
+    /// Use this for scopes which may finish on another thread than they began on, such as
+    /// the body of an `async` function on a multi-threaded runtime. Scopes finishing on
+    /// another thread are never recorded in the tree. The span of the timer is not entered
+    /// either, only the report is emitted inside it. Pass `ScopedTimer::span` to
+    /// `tracing::Instrument` to enter it while a future is polled.
+    #[inline]
+    pub fn detached(timer: Timer<C>) -> Self {
+        Self::with_entered(timer, None, false)
+    }
+
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn with_entered(timer: Timer<C>, entered: Option<tree::Entered>, enter_span: bool) -> Self {
+        let chrome_thread = chrome::begin(&timer.task_name);
+        #[cfg(feature = "tracing")]
+        let span = timer.trace_span.then(|| {
+            let span = tracing::info_span!("took", task = %timer.task_name);
+            // entered through the dispatcher instead of `Span::enter`, so the guard stays
+            // `Send`, which is why the thread is kept to exit it only where it was entered
+            let thread = enter_span.then(|| {
+                span.with_subscriber(|(id, dispatch)| dispatch.enter(id));
+                std::thread::current().id()
+            });
+            (span, thread)
+        });
+        ScopedTimer {
+            timer,
+            entered,
+            finished: false,
+            report: Report::Sink,
+            chrome_thread,
+            #[cfg(feature = "tracing")]
+            span,
+        }
+    }
+
+    /// Makes the guard log its report using the `log` crate instead of writing it to the sink.
+    ///
+    /// The level and target of the timer are used, see [`Timer::set_log_level`]
+    /// and [`Timer::set_log_target`].
+    ///
+    /// This method is only available when the `log` feature is enabled.
+    #[inline]
+    #[cfg(feature = "log")]
+    pub fn report_with_log(mut self) -> Self {
+        self.report = Report::Log;
+        self
+    }
+
+    /// Makes the guard emit its report as a `tracing` event instead of writing it to the sink,
+    /// with `status = "finished"` or `status = "panicked"`.
+    ///
+    /// This method is only available when the `tracing` feature is enabled.
+    #[inline]
+    #[cfg(feature = "tracing")]
+    pub fn report_with_trace(mut self) -> Self {
+        self.report = Report::Trace;
+        self
+    }
+
+    /// Returns the span of the scope, if the timer was set to open one.
+    ///
+    /// This method is only available when the `tracing` feature is enabled.
+    #[inline]
+    #[cfg(feature = "tracing")]
+    pub fn span(&self) -> Option<&tracing::Span> {
+        self.span.as_ref().map(|(span, _)| span)
+    }
+
+    /// Reports the duration of the scope now instead of on drop, and returns it.
+    #[inline]
+    pub fn finish(mut self) -> Duration {
+        let duration = self.timer.duration();
+        self.report(duration);
+        self.close(duration);
+        duration
+    }
+
+    /// Reports the duration of the scope now instead of on drop, and returns a
+    /// [`TimingRecord`] of everything the timer measured.
+    #[inline]
+    pub fn finish_record(mut self) -> TimingRecord {
+        let duration = self.timer.duration();
+        self.report(duration);
+        self.close(duration);
+        self.timer.record_with(duration)
+    }
+
+    /// Logs the duration of the scope now instead of printing it on drop, and returns it.
+    ///
+    /// The level and target of the timer are used, see [`Timer::set_log_level`]
+    /// and [`Timer::set_log_target`]. A duration over the threshold of the timer is
+    /// logged at least at `Warn` level, see [`Timer::set_threshold`].
+    ///
+    /// This method is only available when the `log` feature is enabled.
+    #[inline]
+    #[cfg(feature = "log")]
+    pub fn finish_log(self) -> Duration {
+        self.report_with_log().finish()
+    }
+
+    /// Emits a `tracing` event with `status = "finished"` now instead of printing on drop,
+    /// and returns the duration. The event is emitted inside the span of the scope, if any.
+    ///
+    /// This method is only available when the `tracing` feature is enabled.
+    #[inline]
+    #[cfg(feature = "tracing")]
+    pub fn finish_trace(self) -> Duration {
+        self.report_with_trace().finish()
+    }
+
+    /// Returns the report emitted when the guard is dropped.
+    #[inline]
+    pub fn report_str(&self) -> String {
+        self.message(self.timer.duration())
+    }
+
+    fn message(&self, duration: Duration) -> String {
+        let mut message = self.timer.took_message(duration);
+        if std::thread::panicking() {
+            let end = message.find('\n').unwrap_or(message.len());
+            message.insert_str(end, " (panicked)");
+        }
+        message
+    }
+
+    fn report(&self, duration: Duration) {
+        if registry::is_recording() {
+            registry::record(&self.timer.task_name, duration);
+            return;
+        }
+        // a panicking scope is always reported
+        if !std::thread::panicking() && !self.timer.should_report(duration) {
+            return;
+        }
+        let message = self.message(duration);
+        match self.report {
+            Report::Sink => self.timer.emit(&message),
+            #[cfg(feature = "log")]
+            Report::Log => {
+                let level = self.timer.escalated(self.timer.log_level, duration);
+                self.timer.log_message(level, &message)
+            }
+            #[cfg(feature = "tracing")]
+            Report::Trace => {
+                let (level, status) = if std::thread::panicking() {
+                    (tracing::Level::ERROR, "panicked")
+                } else if self.timer.overrun(duration).is_some() {
+                    (tracing::Level::WARN, "finished")
+                } else {
+                    (tracing::Level::INFO, "finished")
+                };
+                let event = || self.timer.trace_event(level, status, duration, &message);
+                match &self.span {
+                    Some((span, thread)) if *thread != Some(std::thread::current().id()) => {
+                        span.in_scope(event)
+                    }
+                    _ => event(),
+                }
+            }
+        }
+    }
+
+    /// Marks the scope as finished, leaving the timing tree and the span.
+    fn close(&mut self, duration: Duration) {
+        self.finished = true;
+        if let Some(entered) = self.entered.take() {
+            tree::exit(entered, duration);
+        }
+        if let Some(thread_id) = self.chrome_thread.take() {
+            chrome::end(&self.timer.task_name, thread_id);
+        }
+        #[cfg(feature = "tracing")]
+        if let Some((span, Some(thread))) = self.span.take() {
+            if thread == std::thread::current().id() {
+                span.with_subscriber(|(id, dispatch)| dispatch.exit(id));
+            }
+        }
+    }
+}
+
+impl<C: Clock> Deref for ScopedTimer<C> {
+    type Target = Timer<C>;
+
+    #[inline]
+    fn deref(&self) -> &Timer<C> {
+        &self.timer
+    }
+}
+
+impl<C: Clock> DerefMut for ScopedTimer<C> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Timer<C> {
+        &mut self.timer
+    }
+}
+
+impl<C: Clock> Drop for ScopedTimer<C> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let duration = self.timer.duration();
+            self.report(duration);
+            self.close(duration);
+        }
+    }
+}
+
+impl Timer {
+    /// Creates a [`ScopedTimer`] which reports when it goes out of scope.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tea_timer::Timer;
+    ///
+    /// {
+    ///     let _guard = Timer::scoped("block");
+    ///     // ...any code
+    /// } // This will print: "block took ..."
+    /// ```
+    #[inline]
+    pub fn scoped(task_name: &str) -> ScopedTimer {
+        ScopedTimer::new(Timer::new(task_name))
+    }
+}
+
+impl<C: Clock> Timer<C> {
+    /// Turns the timer into a [`ScopedTimer`] which reports when it goes out of scope.
+    #[inline]
+    pub fn into_scoped(self) -> ScopedTimer<C> {
+        ScopedTimer::new(self)
+    }
+}
+
+/// Times the rest of the enclosing scope and reports when the scope is left.
+///
+/// The name defaults to the `file:line` of the invocation.
+///
+/// # Examples
+///
+/// ```
+/// fn work() -> Option<i32> {
+///     tea_timer::timed_scope!("work");
+///     let value = Some(1)?;
+///     Some(value + 1)
+/// } // This will print: "work took ..."
+///
+/// assert_eq!(work(), Some(2));
+/// ```
+#[macro_export]
+macro_rules! timed_scope {
+    () => {
+        let _timed_scope_guard = $crate::Timer::scoped(concat!(file!(), ":", line!()));
+    };
+    ($name:expr) => {
+        let _timed_scope_guard = $crate::Timer::scoped(&$name);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::BufferSink;
+    use crate::ManualClock;
+    use std::time::Duration;
+
+    #[test]
+    fn test_scoped_timer_report() {
+        let clock = ManualClock::new();
+        let mut guard = Timer::with_clock("Scope", clock.clone()).into_scoped();
+        clock.advance(Duration::from_millis(3));
+        guard.lap("first");
+        assert_eq!(guard.laps().len(), 1);
+        assert_eq!(
+            guard.report_str(),
+            "Scope took 3.00ms\n  lap     split   total\n  first  3.00ms  3.00ms"
+        );
+    }
+
+    #[test]
+    fn test_scoped_timer_panic() {
+        let buffer = BufferSink::new();
+        let clock = ManualClock::new();
+        let result = std::panic::catch_unwind(|| {
+            let mut guard = Timer::with_clock("Panicking Scope", clock.clone()).into_scoped();
+            guard.set_sink(buffer.clone());
+            clock.advance(Duration::from_millis(1));
+            panic!("boom");
+        });
+        assert!(result.is_err());
+        assert_eq!(
+            buffer.messages(),
+            ["Panicking Scope took 1.00ms (panicked)"]
+        );
+    }
+
+    #[test]
+    fn test_scoped_timer_threshold() {
+        let buffer = BufferSink::new();
+        let clock = ManualClock::new();
+        for millis in [4, 5, 7] {
+            let mut guard = Timer::with_clock("Budget", clock.clone()).into_scoped();
+            guard.set_sink(buffer.clone());
+            guard.set_threshold(Duration::from_millis(5));
+            clock.advance(Duration::from_millis(millis));
+        }
+        assert_eq!(
+            buffer.messages(),
+            [
+                "Budget took 5.00ms",
+                "Budget took 7.00ms (over budget of 5.00ms by 2.00ms)"
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn test_scoped_timer_span_threads() {
+        use std::sync::{Arc, Mutex};
+        use std::thread::ThreadId;
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+
+        /// Records on which thread the span is entered and exited, and events.
+        #[derive(Clone, Default)]
+        struct Calls(Arc<Mutex<Vec<(&'static str, ThreadId)>>>);
+
+        impl Calls {
+            fn push(&self, call: &'static str) {
+                let thread = std::thread::current().id();
+                self.0.lock().unwrap().push((call, thread));
+            }
+        }
+
+        impl tracing::Subscriber for Calls {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {
+                self.push("event");
+            }
+            fn enter(&self, _: &Id) {
+                self.push("enter");
+            }
+            fn exit(&self, _: &Id) {
+                self.push("exit");
+            }
+        }
+
+        let calls = Calls::default();
+        let here = std::thread::current().id();
+        let there = tracing::subscriber::with_default(calls.clone(), || {
+            let mut timer = Timer::new("Span");
+            timer.set_trace_span(true);
+            timer.into_scoped().report_with_trace().finish();
+
+            let mut timer = Timer::new("Moved");
+            timer.set_trace_span(true);
+            let guard = timer.into_scoped().report_with_trace();
+            let calls = calls.clone();
+            std::thread::spawn(move || {
+                tracing::subscriber::with_default(calls, || drop(guard));
+                std::thread::current().id()
+            })
+            .join()
+            .unwrap()
+        });
+        assert_eq!(
+            *calls.0.lock().unwrap(),
+            [
+                ("enter", here),
+                ("event", here),
+                ("exit", here),
+                ("enter", here),
+                // dropped on another thread, the report is emitted inside the span there
+                ("enter", there),
+                ("event", there),
+                ("exit", there),
+            ]
+        );
+
+        let calls = Calls::default();
+        tracing::subscriber::with_default(calls.clone(), || {
+            let mut timer = Timer::new("Detached");
+            timer.set_trace_span(true);
+            let guard = ScopedTimer::detached(timer).report_with_trace();
+            assert!(guard.span().is_some());
+            guard.finish();
+        });
+        assert_eq!(
+            *calls.0.lock().unwrap(),
+            [("enter", here), ("event", here), ("exit", here)]
+        );
+    }
+
+    #[test]
+    fn test_timed_scope_macro() {
+        fn work(fail: bool) -> Result<i32, ()> {
+            timed_scope!("work");
+            if fail {
+                return Err(());
+            }
+            Ok(1)
+        }
+        assert_eq!(work(false), Ok(1));
+        assert_eq!(work(true), Err(()));
+    }
+}
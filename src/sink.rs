@@ -0,0 +1,206 @@
+//! Destinations for the reports of this crate.
+//!
+//! Every report ([`Timer::elapsed`](crate::Timer::elapsed), [`Timer::stop`](crate::Timer::stop),
+//! [`took`](fn@crate::took), [`took!`](crate::took!), scope guards and the summary reports)
+//! is written to the sink of the timer if one was set with
+//! [`Timer::set_sink`](crate::Timer::set_sink), otherwise to the global sink set
+//! with [`set_global`], which defaults to [`Stdout`].
+//!
+//! # Examples
+//!
+//! ```
+//! use tea_timer::sink::{self, BufferSink, Stderr};
+//! use tea_timer::Timer;
+//!
+//! // keep stdout clean for piped output
+//! sink::set_global(Stderr);
+//!
+//! let buffer = BufferSink::new();
+//! let mut timer = Timer::new("task");
+//! timer.set_sink(buffer.clone());
+//! timer.stop();
+//! assert!(buffer.contents().starts_with("task took"));
+//!
+//! // any closure taking the message works as a sink
+//! sink::set_global(|message: &str| eprintln!("[timing] {message}"));
+//! sink::reset_global();
+//! ```
+
+use std::io::Write;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use crate::{lock_ignoring_poison, read_ignoring_poison, write_ignoring_poison};
+
+/// A destination for reports.
+///
+/// A message may span multiple lines, but never ends with a newline.
+pub trait Sink: Send + Sync {
+    /// Writes a report.
+    fn emit(&self, message: &str);
+}
+
+impl<F: Fn(&str) + Send + Sync> Sink for F {
+    #[inline]
+    fn emit(&self, message: &str) {
+        self(message)
+    }
+}
+
+/// A sink printing to the standard output, this is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stdout;
+
+impl Sink for Stdout {
+    #[inline]
+    fn emit(&self, message: &str) {
+        println!("{message}");
+    }
+}
+
+/// A sink printing to the standard error.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stderr;
+
+impl Sink for Stderr {
+    #[inline]
+    fn emit(&self, message: &str) {
+        eprintln!("{message}");
+    }
+}
+
+/// A sink writing one line per report to any [`Write`], e.g. a file.
+///
+/// Write errors are ignored, reporting never interrupts the measured program.
+#[derive(Debug, Default)]
+pub struct WriterSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> WriterSink<W> {
+    #[inline]
+    pub fn new(writer: W) -> Self {
+        WriterSink {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Returns the underlying writer.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<W: Write + Send> Sink for WriterSink<W> {
+    fn emit(&self, message: &str) {
+        let mut writer = lock_ignoring_poison(&self.writer);
+        let _ = writeln!(writer, "{message}");
+    }
+}
+
+/// A sink collecting reports in memory.
+///
+/// Clones share the same buffer, so a clone can be installed as sink while the
+/// original is used to read the reports.
+#[derive(Debug, Clone, Default)]
+pub struct BufferSink {
+    messages: Arc<Mutex<Vec<String>>>,
+}
+
+impl BufferSink {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the reports collected so far.
+    #[inline]
+    pub fn messages(&self) -> Vec<String> {
+        lock_ignoring_poison(&self.messages).clone()
+    }
+
+    /// Returns the reports collected so far, joined by newlines.
+    #[inline]
+    pub fn contents(&self) -> String {
+        self.messages().join("\n")
+    }
+
+    /// Returns the reports collected so far and clears the buffer.
+    #[inline]
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *lock_ignoring_poison(&self.messages))
+    }
+}
+
+impl Sink for BufferSink {
+    #[inline]
+    fn emit(&self, message: &str) {
+        lock_ignoring_poison(&self.messages).push(message.to_string());
+    }
+}
+
+static GLOBAL_SINK: RwLock<Option<Arc<dyn Sink>>> = RwLock::new(None);
+
+/// Sets the sink used by every report which has no sink of its own.
+#[inline]
+pub fn set_global(sink: impl Sink + 'static) {
+    *write_ignoring_poison(&GLOBAL_SINK) = Some(Arc::new(sink));
+}
+
+/// Restores the default global sink, [`Stdout`].
+#[inline]
+pub fn reset_global() {
+    *write_ignoring_poison(&GLOBAL_SINK) = None;
+}
+
+/// Writes a report to `sink`, or to the global sink if `sink` is `None`.
+pub(crate) fn emit(sink: Option<&dyn Sink>, message: &str) {
+    if let Some(sink) = sink {
+        return sink.emit(message);
+    }
+    let global = read_ignoring_poison(&GLOBAL_SINK).clone();
+    match global {
+        Some(global) => global.emit(message),
+        None => Stdout.emit(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_sink() {
+        let buffer = BufferSink::new();
+        emit(Some(&buffer), "first");
+        emit(Some(&buffer), "second\nline");
+        assert_eq!(buffer.messages(), ["first", "second\nline"]);
+        assert_eq!(buffer.contents(), "first\nsecond\nline");
+        assert_eq!(buffer.take().len(), 2);
+        assert!(buffer.messages().is_empty());
+    }
+
+    #[test]
+    fn test_writer_sink() {
+        let sink = WriterSink::new(Vec::new());
+        sink.emit("a took 1.00ms");
+        sink.emit("b took 2.00ms");
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "a took 1.00ms\nb took 2.00ms\n"
+        );
+    }
+
+    #[test]
+    fn test_closure_sink() {
+        let count = std::sync::atomic::AtomicUsize::new(0);
+        let sink = |message: &str| {
+            assert_eq!(message, "message");
+            count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        };
+        emit(Some(&sink), "message");
+        assert_eq!(count.into_inner(), 1);
+    }
+}
@@ -0,0 +1,421 @@
+//! Hierarchical timings of nested scopes.
+//!
+//! When recording is enabled with [`set_recording`], every
+//! [`ScopedTimer`](crate::ScopedTimer) (and therefore [`took`](fn@crate::took),
+//! [`took!`](crate::took!) and friends) registers itself on a thread-local stack
+//! while it is alive. When it finishes, its duration is added to a node of the
+//! timing tree whose path is the names of the enclosing scoped timers, so repeated
+//! calls of the same nested scope are aggregated into one node.
+//!
+//! Every distinct path is kept until the tree is cleared with [`take`] or [`reset`],
+//! so task names built from dynamic values like request ids grow the tree with every call.
+//!
+//! # Examples
+//!
+//! ```
+//! use tea_timer::{took, tree};
+//!
+//! tree::set_recording(true);
+//! took(
+//!     || {
+//!         for _ in 0..3 {
+//!             took(|| (), "inner");
+//!         }
+//!     },
+//!     "outer",
+//! );
+//! tree::set_recording(false);
+//! let roots = tree::snapshot();
+//! assert_eq!(roots[0].name, "outer");
+//! assert_eq!(roots[0].children[0].calls, 3);
+//! tree::report(); // prints the tree with total time, self time and percentage of parent
+//! tree::write_folded(std::io::stdout()).unwrap(); // prints "outer <µs>" and "outer;inner <µs>"
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use crate::{display, lock_ignoring_poison, sink};
+
+/// A node of the timing tree, aggregating all calls of a scope with the same path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimingNode {
+    pub name: String,
+    /// Number of times the scope finished.
+    pub calls: u64,
+    /// Total duration of all calls.
+    pub total: Duration,
+    pub children: Vec<TimingNode>,
+}
+
+impl TimingNode {
+    /// Returns the time spent in this node which is not covered by its children.
+    #[inline]
+    pub fn self_time(&self) -> Duration {
+        let children: Duration = self.children.iter().map(|child| child.total).sum();
+        self.total.saturating_sub(children)
+    }
+}
+
+/// A node of the recorded tree, with its children indexed by name.
+#[derive(Default)]
+struct Node {
+    calls: u64,
+    total: Duration,
+    /// Children in the order of their first call.
+    children: Vec<(String, Node)>,
+    index: HashMap<String, usize>,
+}
+
+impl Node {
+    /// Finds the node at `path` below this node, inserting missing nodes on the way.
+    fn find_or_insert<'a>(&mut self, path: impl IntoIterator<Item = &'a str>) -> &mut Node {
+        let mut node = self;
+        for name in path {
+            let idx = match node.index.get(name) {
+                Some(&idx) => idx,
+                None => {
+                    node.index.insert(name.to_string(), node.children.len());
+                    node.children.push((name.to_string(), Node::default()));
+                    node.children.len() - 1
+                }
+            };
+            node = &mut node.children[idx].1;
+        }
+        node
+    }
+
+    fn timing_nodes(&self) -> Vec<TimingNode> {
+        self.children
+            .iter()
+            .map(|(name, node)| TimingNode {
+                name: name.clone(),
+                calls: node.calls,
+                total: node.total,
+                children: node.timing_nodes(),
+            })
+            .collect()
+    }
+}
+
+/// A scope which is currently active.
+struct Frame {
+    id: u64,
+    name: String,
+}
+
+/// The active scopes of a thread, outermost first.
+///
+/// The stack is shared, so a scope finishing on another thread can remove its frame.
+type Stack = Arc<Mutex<Vec<Frame>>>;
+
+thread_local! {
+    /// The unnamed parent of the root nodes recorded on the thread.
+    static TREE: RefCell<Node> = RefCell::new(Node::default());
+    static STACK: Stack = Stack::default();
+}
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables recording of scoped timers into the timing tree of their thread.
+#[inline]
+pub fn set_recording(enabled: bool) {
+    RECORDING.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns `true` if scoped timers are recorded into the timing tree.
+#[inline]
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// An entered scope, to pass to [`exit`].
+#[derive(Debug)]
+pub(crate) struct Entered {
+    id: u64,
+    stack: Weak<Mutex<Vec<Frame>>>,
+}
+
+/// Pushes a scope onto the stack of the current thread if recording.
+pub(crate) fn enter(name: &str) -> Option<Entered> {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    if !is_recording() {
+        return None;
+    }
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    STACK.with(|stack| {
+        lock_ignoring_poison(stack).push(Frame {
+            id,
+            name: name.to_string(),
+        });
+        Some(Entered {
+            id,
+            stack: Arc::downgrade(stack),
+        })
+    })
+}
+
+/// Pops the scope (and any scope left open inside it) and records its duration.
+///
+/// The scope is recorded even if recording was disabled meanwhile, so the stack stays balanced.
+/// A scope exited on another thread than it was entered on is removed from the stack of its
+/// thread without being recorded.
+pub(crate) fn exit(entered: Entered, duration: Duration) {
+    // the thread the scope was entered on may have finished already
+    let Some(stack) = entered.stack.upgrade() else {
+        return;
+    };
+    let mut frames = lock_ignoring_poison(&stack);
+    // the scope may have been popped already by an enclosing scope, e.g. if it was leaked
+    let Some(pos) = frames.iter().position(|frame| frame.id == entered.id) else {
+        return;
+    };
+    let same_thread = STACK
+        .try_with(|own| Arc::ptr_eq(own, &stack))
+        .unwrap_or(false);
+    if !same_thread {
+        frames.remove(pos);
+        return;
+    }
+    let _ = TREE.try_with(|root| {
+        let mut root = root.borrow_mut();
+        let node = root.find_or_insert(frames[..=pos].iter().map(|frame| frame.name.as_str()));
+        node.calls += 1;
+        node.total += duration;
+    });
+    frames.truncate(pos);
+}
+
+/// Returns a copy of the timing tree recorded on the current thread.
+pub fn snapshot() -> Vec<TimingNode> {
+    TREE.with(|root| root.borrow().timing_nodes())
+}
+
+/// Returns the timing tree recorded on the current thread and clears it.
+pub fn take() -> Vec<TimingNode> {
+    TREE.with(|root| std::mem::take(&mut *root.borrow_mut()).timing_nodes())
+}
+
+/// Clears the timing tree recorded on the current thread.
+///
+/// Scopes which are still active are kept on the stack.
+#[inline]
+pub fn reset() {
+    take();
+}
+
+/// Returns the timing tree of the current thread as an indented table.
+///
+/// Each row shows the number of calls, the total time, the self time and the
+/// percentage of the parent's total time.
+pub fn report_str() -> String {
+    format_nodes(&snapshot())
+}
+
+/// Prints the timing tree of the current thread to the global [`sink`].
+#[inline]
+pub fn report() {
+    sink::emit(None, &report_str());
+}
+
+/// Writes the timing tree of the current thread as folded stacks, see [`write_folded_nodes`].
+#[inline]
+pub fn write_folded(writer: impl Write) -> io::Result<()> {
+    write_folded_nodes(writer, &snapshot())
+}
+
+/// Returns the timing tree of the current thread as folded stacks, see [`write_folded_nodes`].
+pub fn folded_str() -> String {
+    let mut folded = Vec::new();
+    write_folded(&mut folded).expect("writing to a Vec cannot fail");
+    String::from_utf8(folded).expect("folded stacks are valid UTF-8")
+}
+
+/// Writes the given timing tree as folded stacks, one `outer;inner;leaf <microseconds>`
+/// line per node with the self time of the node aggregated across all of its calls.
+///
+/// The output can be piped into `inferno-flamegraph` or `flamegraph.pl`. `;` in task
+/// names is replaced with `:`, and nodes with less than a microsecond of self time
+/// are omitted.
+///
+/// # Examples
+///
+/// ```
+/// use tea_timer::tree::{write_folded_nodes, TimingNode};
+/// use std::time::Duration;
+///
+/// let leaf = TimingNode {
+///     name: "parse".to_string(),
+///     calls: 2,
+///     total: Duration::from_millis(3),
+///     children: Vec::new(),
+/// };
+/// let root = TimingNode {
+///     name: "main".to_string(),
+///     calls: 1,
+///     total: Duration::from_millis(5),
+///     children: vec![leaf],
+/// };
+/// let mut folded = Vec::new();
+/// write_folded_nodes(&mut folded, &[root]).unwrap();
+/// assert_eq!(String::from_utf8(folded).unwrap(), "main 2000\nmain;parse 3000\n");
+/// ```
+pub fn write_folded_nodes(mut writer: impl Write, roots: &[TimingNode]) -> io::Result<()> {
+    fn write_nodes(
+        writer: &mut dyn Write,
+        nodes: &[TimingNode],
+        stack: &mut String,
+    ) -> io::Result<()> {
+        for node in nodes {
+            let len = stack.len();
+            if len > 0 {
+                stack.push(';');
+            }
+            stack.extend(node.name.chars().map(|c| match c {
+                ';' => ':',
+                '\n' | '\r' => ' ',
+                c => c,
+            }));
+            let micros = node.self_time().as_micros();
+            if micros > 0 {
+                writeln!(writer, "{stack} {micros}")?;
+            }
+            write_nodes(writer, &node.children, stack)?;
+            stack.truncate(len);
+        }
+        Ok(())
+    }
+
+    write_nodes(&mut writer, roots, &mut String::new())
+}
+
+fn format_nodes(roots: &[TimingNode]) -> String {
+    fn collect(
+        nodes: &[TimingNode],
+        parent: Option<Duration>,
+        level: usize,
+        rows: &mut Vec<[String; 5]>,
+    ) {
+        for node in nodes {
+            let percent = match parent {
+                Some(parent) if !parent.is_zero() => format!(
+                    "{:.1}%",
+                    node.total.as_secs_f64() / parent.as_secs_f64() * 100.
+                ),
+                _ => "-".to_string(),
+            };
+            rows.push([
+                format!("{}{}", "  ".repeat(level), node.name),
+                node.calls.to_string(),
+                display::format_duration(node.total),
+                display::format_duration(node.self_time()),
+                percent,
+            ]);
+            collect(&node.children, Some(node.total), level + 1, rows);
+        }
+    }
+
+    let mut rows = vec![["name", "calls", "total", "self", "parent"].map(String::from)];
+    collect(roots, None, 0, &mut rows);
+    display::format_table(&rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ManualClock, Timer};
+
+    #[test]
+    fn test_tree_nesting() {
+        set_recording(true);
+        reset();
+        let clock = ManualClock::new();
+        let outer = Timer::with_clock("outer", clock.clone()).into_scoped();
+        for _ in 0..2 {
+            let inner = Timer::with_clock("inner", clock.clone()).into_scoped();
+            clock.advance(Duration::from_millis(10));
+            inner.finish();
+        }
+        clock.advance(Duration::from_millis(5));
+        outer.finish();
+
+        let roots = take();
+        assert_eq!(roots.len(), 1);
+        let outer = &roots[0];
+        assert_eq!(outer.calls, 1);
+        assert_eq!(outer.total, Duration::from_millis(25));
+        assert_eq!(outer.self_time(), Duration::from_millis(5));
+        assert_eq!(outer.children[0].name, "inner");
+        assert_eq!(outer.children[0].calls, 2);
+        assert_eq!(outer.children[0].total, Duration::from_millis(20));
+        assert_eq!(
+            format_nodes(&roots),
+            [
+                "name     calls    total     self  parent",
+                "outer        1  25.00ms   5.00ms       -",
+                "  inner      2  20.00ms  20.00ms   80.0%",
+            ]
+            .join("\n")
+        );
+        assert!(snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_tree_folded() {
+        set_recording(true);
+        reset();
+        let clock = ManualClock::new();
+        for _ in 0..3 {
+            let outer = Timer::with_clock("outer", clock.clone()).into_scoped();
+            let inner = Timer::with_clock("in;ner", clock.clone()).into_scoped();
+            let leaf = Timer::with_clock("leaf", clock.clone()).into_scoped();
+            clock.advance(Duration::from_micros(10));
+            leaf.finish();
+            // no self time for `inner`
+            inner.finish();
+            clock.advance(Duration::from_micros(1));
+            outer.finish();
+        }
+        assert_eq!(folded_str(), "outer 3\nouter;in:ner;leaf 30\n");
+        reset();
+        assert_eq!(folded_str(), "");
+    }
+
+    #[test]
+    fn test_tree_unclosed_scope() {
+        set_recording(true);
+        reset();
+        let outer = Timer::scoped("outer");
+        let leaked = Timer::scoped("leaked");
+        std::mem::forget(leaked);
+        drop(outer);
+        let roots = take();
+        assert_eq!(roots[0].name, "outer");
+        assert_eq!(roots[0].calls, 1);
+        assert!(roots[0].children.is_empty());
+        Timer::scoped("next").finish();
+        assert_eq!(take()[0].name, "next");
+    }
+
+    #[test]
+    fn test_tree_scope_moved_to_thread() {
+        set_recording(true);
+        reset();
+        let moved = Timer::scoped("moved");
+        let inner = Timer::scoped("inner");
+        std::thread::spawn(move || drop(moved)).join().unwrap();
+        drop(inner);
+        Timer::scoped("after").finish();
+        // `moved` finished on another thread, so it is neither recorded nor left on the stack
+        let roots = take();
+        let names: Vec<_> = roots.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, ["inner", "after"]);
+        assert!(roots
+            .iter()
+            .all(|node| node.calls == 1 && node.children.is_empty()));
+    }
+}
@@ -0,0 +1,247 @@
+//! Procedural macros for [`tea-timer`](https://docs.rs/tea-timer).
+//!
+//! Use them through the `macros` feature of `tea-timer` rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Block, ItemFn, LitStr};
+
+/// Times every call of a function and reports when the call returns.
+///
+/// Works with sync and `async` functions, the signature, the return type, early
+/// `return`s and `?` are left untouched. The report is emitted through
+/// `tea_timer::ScopedTimer`, so panics are reported as well and nested timed
+/// functions show up in `tea_timer::tree` when it is recording. Calls of `async`
+/// functions are left out of the tree, as they may resume on another thread.
+///
+/// # Options
+///
+/// - `name = "parse"`: the task name, defaults to the path of the function, e.g. `my_crate::io::parse`
+///   or `my_crate::io::Parser::new` for methods
+/// - `log`: log the report using the `log` crate instead of printing it
+/// - `level = "debug"`: the level of the log record, implies `log`
+/// - `threshold = "5ms"`: skip the report if the call took less than the threshold, and
+///   report by how much the budget was overrun otherwise
+///
+/// # Examples
+///
+/// ```
+/// # #![deny(unused_braces)]
+/// use tea_timer::timed;
+///
+/// #[timed]
+/// fn parse(input: &str) -> Result<i32, std::num::ParseIntError> {
+///     let value = input.parse::<i32>()?;
+///     Ok(value * 2)
+/// }
+///
+/// #[timed]
+/// fn answer() -> u32 { 42 }
+///
+/// #[timed(name = "load", threshold = "10ms")]
+/// async fn load() -> usize {
+///     42
+/// }
+///
+/// # fn main() {
+/// assert_eq!(parse("21"), Ok(42));
+/// assert!(parse("x").is_err());
+/// assert_eq!(answer(), 42);
+/// # }
+/// ```
+#[proc_macro_attribute]
+pub fn timed(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = Options::default();
+    let parser = syn::meta::parser(|meta| options.parse(meta));
+    parse_macro_input!(attr with parser);
+    let mut function = parse_macro_input!(item as ItemFn);
+
+    let name = match &options.name {
+        Some(name) => quote!(#name),
+        // the path of a nested function includes the impl type, unlike `module_path!`,
+        // and the body of an `async` function adds a `{{closure}}` segment
+        None => quote!({
+            fn __tea_timer_path() {}
+            let path = ::std::any::type_name_of_val(&__tea_timer_path);
+            let path = path.strip_suffix("::__tea_timer_path").unwrap_or(path);
+            path.trim_end_matches("::{{closure}}")
+        }),
+    };
+    let threshold = options
+        .threshold
+        .map(|nanos| quote!(timer.set_threshold(::core::time::Duration::from_nanos(#nanos));));
+    let level = options.level.as_ref().map(|level| {
+        let level = format_ident!("{}", level);
+        quote!(timer.set_log_level(::tea_timer::__private::log::Level::#level);)
+    });
+    let report = options.log.then(|| quote!(.report_with_log()));
+    let scoped = if function.sig.asyncness.is_some() {
+        quote!(::tea_timer::ScopedTimer::detached(timer))
+    } else {
+        quote!(timer.into_scoped())
+    };
+
+    // the statements are spliced rather than nesting the block, which would trigger
+    // `unused_braces` for bodies consisting of a single expression
+    let stmts = &function.block.stmts;
+    let block: Block = parse_quote!({
+        let __tea_timer_guard = {
+            #[allow(unused_mut)]
+            let mut timer = ::tea_timer::Timer::new(#name);
+            #threshold
+            #level
+            #scoped #report
+        };
+        #(#stmts)*
+    });
+    *function.block = block;
+    quote!(#function).into()
+}
+
+#[derive(Default)]
+struct Options {
+    name: Option<LitStr>,
+    log: bool,
+    /// Name of the `log::Level` variant.
+    level: Option<&'static str>,
+    /// Threshold in nanoseconds.
+    threshold: Option<u64>,
+}
+
+impl Options {
+    fn parse(&mut self, meta: syn::meta::ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("log") {
+            self.log = true;
+        } else if meta.path.is_ident("level") {
+            let level: LitStr = meta.value()?.parse()?;
+            self.level = Some(match level.value().to_ascii_lowercase().as_str() {
+                "error" => "Error",
+                "warn" => "Warn",
+                "info" => "Info",
+                "debug" => "Debug",
+                "trace" => "Trace",
+                _ => {
+                    return Err(syn::Error::new(
+                        level.span(),
+                        "expected one of error, warn, info, debug, trace",
+                    ))
+                }
+            });
+            self.log = true;
+        } else if meta.path.is_ident("threshold") {
+            let threshold: LitStr = meta.value()?.parse()?;
+            let nanos = parse_nanos(&threshold.value())
+                .map_err(|msg| syn::Error::new(threshold.span(), msg))?;
+            self.threshold = Some(nanos);
+        } else {
+            return Err(
+                meta.error("unsupported option, expected one of name, log, level, threshold")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Parses a duration such as `5ms`, `1.5s`, `5 ms` or `1m30s` into nanoseconds.
+///
+/// This accepts the same inputs as `tea_timer::parse_duration`, which cannot be
+/// used here as `tea-timer` depends on this crate.
+fn parse_nanos(input: &str) -> Result<u64, String> {
+    let error = || format!("invalid duration `{input}`, expected e.g. `5ms`, `1.5s` or `1m30s`");
+    let mut rest = input.trim();
+    if rest.is_empty() {
+        return Err(error());
+    }
+    let mut total: u128 = 0;
+    let mut last_unit_nanos = u128::MAX;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(error)?;
+        let (number, tail) = rest.split_at(number_len);
+        let tail = tail.trim_start();
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c.is_whitespace())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let unit_nanos: u128 = match unit {
+            "ns" => 1,
+            "µs" | "μs" | "us" => 1_000,
+            "ms" => 1_000_000,
+            "s" => 1_000_000_000,
+            "m" => 60_000_000_000,
+            "h" => 3_600_000_000_000,
+            "d" => 86_400_000_000_000,
+            _ => return Err(error()),
+        };
+        // units must be in descending order, as in `1h2m3s`
+        if unit_nanos >= last_unit_nanos {
+            return Err(error());
+        }
+        last_unit_nanos = unit_nanos;
+        let (int, frac) = number.split_once('.').unwrap_or((number, ""));
+        if int.is_empty() && frac.is_empty() {
+            return Err(error());
+        }
+        let int: u128 = if int.is_empty() {
+            0
+        } else {
+            int.parse().map_err(|_| error())?
+        };
+        let mut nanos = int.checked_mul(unit_nanos).ok_or_else(error)?;
+        let frac = &frac[..frac.len().min(24)];
+        if !frac.is_empty() {
+            let scale = 10u128.pow(frac.len() as u32);
+            let frac: u128 = frac.parse().map_err(|_| error())?;
+            nanos += (2 * frac * unit_nanos + scale) / (2 * scale);
+        }
+        total = total.checked_add(nanos).ok_or_else(error)?;
+        rest = tail.trim_start();
+    }
+    u64::try_from(total).map_err(|_| error())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nanos() {
+        // the same inputs as the tests of `tea_timer::parse_duration`
+        for (input, expected) in [
+            ("800ns", 800),
+            ("12.34ms", 12_340_000),
+            ("5.00s", 5_000_000_000),
+            ("1.50µs", 1_500),
+            ("1.50μs", 1_500),
+            ("1.50us", 1_500),
+            (".5s", 500_000_000),
+            ("2.h", 7_200_000_000_000),
+            ("1h2m3.5s", 3_723_500_000_000),
+            (" 1d 1h ", 90_000_000_000_000),
+            ("1m 23.4s", 83_400_000_000),
+            ("3 ms", 3_000_000),
+            ("0.0000000005s", 1),
+            ("1.0000000004s", 1_000_000_000),
+        ] {
+            assert_eq!(parse_nanos(input), Ok(expected), "{input}");
+        }
+        for input in [
+            "",
+            "  ",
+            "ms",
+            "-1s",
+            "12",
+            "12 3s",
+            "1s 5",
+            "3 weeks",
+            "1s2h",
+            "1s1s",
+            "1000000000000000000000000d",
+        ] {
+            assert!(parse_nanos(input).is_err(), "{input}");
+        }
+    }
+}